@@ -0,0 +1,271 @@
+use crate::error::HeadlessCliError;
+use crate::validation::HTR_UID;
+
+use std::fmt;
+use std::str::FromStr;
+
+/////////////////////////////////////////// Amounts
+
+/// Number of decimal places of hathor amounts, 1.00 HTR is 100 units.
+pub const DECIMAL_PLACES: usize = 2;
+
+const UNITS_PER_TOKEN: u64 = 100;
+
+/// An amount in the smallest unit of a token (e.g. 1234 units is 12.34 HTR).
+///
+/// # Examples
+///
+/// ```
+/// use headless_cli::amount::Amount;
+///
+/// assert_eq!(Amount::from_units(1234).to_string(), "12.34");
+/// assert_eq!(Amount::from_units(5).to_string(), "0.05");
+/// assert_eq!(Amount::from_units(u64::MAX).to_string(), "184467440737095516.15");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Amount(u64);
+
+impl Amount {
+    pub fn from_units(units: u64) -> Self {
+        Amount(units)
+    }
+
+    /// The amount in the smallest unit of the token, as expected by the headless.
+    pub fn units(&self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{:0width$}",
+            self.0 / UNITS_PER_TOKEN,
+            self.0 % UNITS_PER_TOKEN,
+            width = DECIMAL_PLACES
+        )
+    }
+}
+
+/// An amount as typed on the command line.
+///
+/// Accepts a decimal value with at most 2 decimal places and an optional token
+/// symbol suffix, e.g. `12.00`, `12.34`, `12HTR` or `12.34HTR`.
+/// A bare integer (`1234`) is only accepted as units with `--raw-units`, since
+/// it used to mean units and now would mean 100 times more tokens. With
+/// `--raw-units` only bare integers are accepted.
+/// The value is only converted to units with [`AmountArg::to_amount`].
+///
+/// # Examples
+///
+/// ```
+/// use headless_cli::amount::AmountArg;
+///
+/// let arg: AmountArg = "12.34HTR".parse().unwrap();
+/// assert_eq!(arg.to_amount(false).unwrap().units(), 1234);
+/// assert_eq!(arg.symbol(), Some("HTR"));
+///
+/// let arg: AmountArg = "12HTR".parse().unwrap();
+/// assert_eq!(arg.to_amount(false).unwrap().units(), 1200);
+///
+/// // Bare integers are ambiguous without --raw-units
+/// let arg: AmountArg = "1234".parse().unwrap();
+/// assert!(arg.to_amount(false).is_err());
+/// assert_eq!(arg.to_amount(true).unwrap().units(), 1234);
+///
+/// // Decimal values and symbols are not valid raw units
+/// let arg: AmountArg = "12.3".parse().unwrap();
+/// assert!(arg.to_amount(true).is_err());
+/// let arg: AmountArg = "12HTR".parse().unwrap();
+/// assert!(arg.to_amount(true).is_err());
+///
+/// assert!("12.345".parse::<AmountArg>().is_err());
+/// assert!("12,34".parse::<AmountArg>().is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AmountArg {
+    integer: u64,
+    /// Decimal part in units, `None` if the value has no decimal point
+    decimals: Option<u64>,
+    symbol: Option<String>,
+}
+
+impl AmountArg {
+    /// Token symbol typed after the value, if any.
+    pub fn symbol(&self) -> Option<&str> {
+        self.symbol.as_deref()
+    }
+
+    /// Reject a typed symbol that is not `expected`, the symbol of the token of the amount.
+    ///
+    /// `expected` is `None` when the symbol of the token is not known, then no
+    /// symbol can be typed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use headless_cli::amount::AmountArg;
+    ///
+    /// let arg: AmountArg = "12htr".parse().unwrap();
+    /// assert!(arg.check_symbol(Some("HTR")).is_ok());
+    /// assert!(arg.check_symbol(Some("MYT")).is_err());
+    /// assert!(arg.check_symbol(None).is_err());
+    ///
+    /// let arg: AmountArg = "12.00".parse().unwrap();
+    /// assert!(arg.check_symbol(None).is_ok());
+    /// ```
+    pub fn check_symbol(&self, expected: Option<&str>) -> Result<(), HeadlessCliError> {
+        match (self.symbol(), expected) {
+            (None, _) => Ok(()),
+            (Some(symbol), Some(expected)) if symbol.eq_ignore_ascii_case(expected) => Ok(()),
+            (Some(symbol), Some(expected)) => Err(HeadlessCliError::InvalidInput(format!(
+                "{} is an amount of {} but the token is {}",
+                self, symbol, expected
+            ))),
+            (Some(symbol), None) => Err(HeadlessCliError::InvalidInput(format!(
+                "the symbol {} of {} cannot be checked for this token, remove it",
+                symbol, self
+            ))),
+        }
+    }
+
+    /// Convert to an amount in units of `token` (HTR if not set), see [`AmountArg::check_symbol`].
+    ///
+    /// # Arguments
+    ///
+    /// * `raw_units` - Interpret the value as units instead of a decimal amount
+    /// * `token` - Uid of the token of the amount
+    /// * `token_symbol` - Symbol of `token` when it is a custom token, if known
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use headless_cli::amount::AmountArg;
+    ///
+    /// let arg: AmountArg = "12HTR".parse().unwrap();
+    /// assert_eq!(arg.to_token_amount(false, None, None).unwrap().units(), 1200);
+    /// let uid = "ab".repeat(32);
+    /// assert!(arg.to_token_amount(false, Some(&uid), Some("MYT")).is_err());
+    /// assert!(arg.to_token_amount(false, Some(&uid), None).is_err());
+    /// ```
+    pub fn to_token_amount(
+        &self,
+        raw_units: bool,
+        token: Option<&str>,
+        token_symbol: Option<&str>,
+    ) -> Result<Amount, HeadlessCliError> {
+        let expected = match token {
+            None | Some(HTR_UID) => Some("HTR"),
+            Some(token) => {
+                // HTR is never the symbol of a custom token, even if its symbol is not known
+                let htr = self
+                    .symbol()
+                    .is_some_and(|symbol| symbol.eq_ignore_ascii_case("HTR"));
+                if token_symbol.is_none() && htr {
+                    return Err(HeadlessCliError::InvalidInput(format!(
+                        "{} is an amount of HTR but the token is {}",
+                        self, token
+                    )));
+                }
+                token_symbol
+            }
+        };
+        self.check_symbol(expected)?;
+        self.to_amount(raw_units)
+    }
+
+    /// Convert to an amount in units.
+    ///
+    /// # Arguments
+    ///
+    /// * `raw_units` - Interpret the value as units instead of a decimal amount
+    ///
+    pub fn to_amount(&self, raw_units: bool) -> Result<Amount, HeadlessCliError> {
+        if raw_units {
+            return match (self.decimals, &self.symbol) {
+                (None, None) => Ok(Amount(self.integer)),
+                // A symbol means an amount of tokens, not units
+                _ => Err(HeadlessCliError::InvalidInput(format!(
+                    "{} is not an integer amount of units",
+                    self
+                ))),
+            };
+        }
+        if self.decimals.is_none() && self.symbol.is_none() {
+            return Err(HeadlessCliError::InvalidInput(format!(
+                "{} is ambiguous, use {}.00 for tokens or --raw-units for units",
+                self, self
+            )));
+        }
+
+        self.integer
+            .checked_mul(UNITS_PER_TOKEN)
+            .and_then(|units| units.checked_add(self.decimals.unwrap_or(0)))
+            .map(Amount)
+            .ok_or_else(|| HeadlessCliError::InvalidInput(format!("{} is too big", self)))
+    }
+}
+
+impl FromStr for AmountArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let symbol_start = s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len());
+        let (number, symbol) = s.split_at(symbol_start);
+
+        if !symbol.is_empty() && !symbol.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(format!("invalid token symbol: {}", symbol));
+        }
+
+        let (integer, decimals) = match number.split_once('.') {
+            Some((integer, decimals)) => (integer, Some(decimals)),
+            None => (number, None),
+        };
+
+        if integer.is_empty() || !integer.chars().all(|c| c.is_ascii_digit()) {
+            return Err(format!("invalid amount: {}", s));
+        }
+        let integer = integer
+            .parse::<u64>()
+            .map_err(|_| format!("amount is too big: {}", s))?;
+
+        let decimals = match decimals {
+            Some(decimals) => {
+                if decimals.is_empty() || !decimals.chars().all(|c| c.is_ascii_digit()) {
+                    return Err(format!("invalid amount: {}", s));
+                }
+                if decimals.len() > DECIMAL_PLACES {
+                    return Err(format!(
+                        "amounts have at most {} decimal places: {}",
+                        DECIMAL_PLACES, s
+                    ));
+                }
+                // "12.3" means 12.30
+                let padded = format!("{:0<width$}", decimals, width = DECIMAL_PLACES);
+                Some(padded.parse::<u64>().map_err(|err| err.to_string())?)
+            }
+            None => None,
+        };
+
+        Ok(AmountArg {
+            integer,
+            decimals,
+            symbol: (!symbol.is_empty()).then(|| symbol.to_string()),
+        })
+    }
+}
+
+impl fmt::Display for AmountArg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.integer)?;
+        if let Some(decimals) = self.decimals {
+            write!(f, ".{:0width$}", decimals, width = DECIMAL_PLACES)?;
+        }
+        if let Some(symbol) = &self.symbol {
+            write!(f, "{}", symbol)?;
+        }
+        Ok(())
+    }
+}
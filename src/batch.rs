@@ -0,0 +1,690 @@
+//! Batch payouts from a CSV or JSON file.
+//!
+//! Every row is validated before anything is sent, rows are grouped into as
+//! few `send-tx` transactions as the maximum number of outputs allows and the
+//! status of each row is saved on a results file after every step, so an
+//! interrupted run can be resumed without paying a row twice.
+
+use crate::amount::AmountArg;
+use crate::client::HeadlessClient;
+use crate::data::SendTxResponse;
+use crate::error::HeadlessCliError;
+use crate::params::ParamsWalletSend;
+use crate::send::{build_send_tx_body, SendOutput};
+use crate::utils::{read_json_file, write_json_file};
+use crate::validation::{validate_address_network, validate_token_uid, Network, HTR_UID};
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/////////////////////////////////////////// Batch payouts
+
+/// Maximum number of outputs of a transaction when the headless does not report it.
+pub const DEFAULT_MAX_OUTPUTS: usize = 255;
+
+/// Status of a row on the results file.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RowStatus {
+    /// Not sent yet
+    Pending,
+    /// The transaction was being sent when the run stopped, it may or may not have been sent
+    Submitting,
+    /// Paid, the transaction id is on the row
+    Sent,
+    /// The headless rejected the transaction, the row will be sent again on the next run
+    Failed,
+}
+
+/// A payout with its status.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BatchRow {
+    /// Line (csv) or position (json) of the row on the source file, starting at 1
+    pub row: usize,
+    pub address: String,
+    /// Amount in units
+    pub value: u64,
+    pub token: String,
+    pub timelock: Option<u64>,
+    pub status: RowStatus,
+    pub tx_id: Option<String>,
+    pub error: Option<String>,
+}
+
+impl BatchRow {
+    /// Same payout, ignoring the status.
+    fn same_payout(&self, other: &BatchRow) -> bool {
+        self.row == other.row
+            && self.address == other.address
+            && self.value == other.value
+            && self.token == other.token
+            && self.timelock == other.timelock
+    }
+
+    fn to_output(&self) -> SendOutput {
+        SendOutput::Value {
+            address: self.address.clone(),
+            value: self.value,
+            token: (self.token != HTR_UID).then(|| self.token.clone()),
+            timelock: self.timelock,
+        }
+    }
+}
+
+/// A row as written on a json source file, amounts may be numbers or strings.
+#[derive(Deserialize)]
+struct JsonRow {
+    address: String,
+    amount: Value,
+    token: Option<String>,
+    timelock: Option<u64>,
+}
+
+/// Message of a row error, without the `Invalid input` prefix.
+fn row_error(err: HeadlessCliError) -> String {
+    match err {
+        HeadlessCliError::InvalidInput(message) => message,
+        err => err.to_string(),
+    }
+}
+
+/// Validate one row, amounts follow the rules of [`AmountArg`].
+fn parse_row(
+    row: usize,
+    address: &str,
+    amount: &str,
+    token: Option<&str>,
+    timelock: Option<u64>,
+    raw_units: bool,
+    network: Option<Network>,
+) -> Result<BatchRow, String> {
+    validate_address_network(address, network).map_err(row_error)?;
+
+    let token = token.filter(|token| !token.is_empty()).unwrap_or(HTR_UID);
+    validate_token_uid(token).map_err(row_error)?;
+
+    // Symbols of custom tokens are not known offline
+    let value = amount
+        .parse::<AmountArg>()?
+        .to_token_amount(raw_units, Some(token), None)
+        .map_err(row_error)?
+        .units();
+    if value == 0 {
+        return Err("amount must not be zero".to_string());
+    }
+
+    Ok(BatchRow {
+        row,
+        address: address.to_string(),
+        value,
+        token: token.to_string(),
+        timelock,
+        status: RowStatus::Pending,
+        tx_id: None,
+        error: None,
+    })
+}
+
+/// Parse and validate every row of a payout file.
+///
+/// CSV files have the columns `address,amount[,token][,timelock]`, with an
+/// optional header line, empty lines and lines starting with `#` are ignored.
+/// JSON files are a list of `{ "address", "amount", "token"?, "timelock"? }`.
+/// All invalid rows are reported together, addresses of other networks than
+/// `network` are invalid.
+///
+/// # Examples
+///
+/// ```
+/// use headless_cli::batch::parse_rows;
+///
+/// let rows = parse_rows("address,amount\nWewDeXWyvHP7jJTs7tjLoQfoB72LLxJQqN,12.34\n", false, false, None).unwrap();
+/// assert_eq!(rows[0].row, 2);
+/// assert_eq!(rows[0].value, 1234);
+/// assert_eq!(rows[0].token, "00");
+///
+/// let rows = parse_rows(r#"[{"address": "WewDeXWyvHP7jJTs7tjLoQfoB72LLxJQqN", "amount": 5}]"#, true, true, None).unwrap();
+/// assert_eq!(rows[0].value, 5);
+///
+/// let err = parse_rows("WewDeXWyvHP7jJTs7tjLoQfoB72LLxJQqN,0.00\nbad,1.00\n", false, false, None).unwrap_err();
+/// assert!(err.to_string().contains("row 1"));
+/// assert!(err.to_string().contains("row 2"));
+/// ```
+pub fn parse_rows(
+    content: &str,
+    json: bool,
+    raw_units: bool,
+    network: Option<Network>,
+) -> Result<Vec<BatchRow>, HeadlessCliError> {
+    let mut rows = vec![];
+    let mut errors = vec![];
+
+    if json {
+        let json_rows: Vec<JsonRow> = serde_json::from_str(content).map_err(|err| {
+            HeadlessCliError::InvalidInput(format!("invalid payout file: {}", err))
+        })?;
+        for (i, json_row) in json_rows.iter().enumerate() {
+            let amount = match &json_row.amount {
+                Value::String(amount) => amount.clone(),
+                amount => amount.to_string(),
+            };
+            match parse_row(
+                i + 1,
+                &json_row.address,
+                &amount,
+                json_row.token.as_deref(),
+                json_row.timelock,
+                raw_units,
+                network,
+            ) {
+                Ok(row) => rows.push(row),
+                Err(err) => errors.push(format!("row {}: {}", i + 1, err)),
+            }
+        }
+    } else {
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || (i == 0 && line.starts_with("address")) {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            if !(2..=4).contains(&fields.len()) {
+                errors.push(format!(
+                    "row {}: expected address,amount[,token][,timelock]",
+                    i + 1
+                ));
+                continue;
+            }
+            let timelock = match fields.get(3).filter(|timelock| !timelock.is_empty()) {
+                Some(timelock) => match timelock.parse::<u64>() {
+                    Ok(timelock) => Some(timelock),
+                    Err(_) => {
+                        errors.push(format!("row {}: invalid timelock: {}", i + 1, timelock));
+                        continue;
+                    }
+                },
+                None => None,
+            };
+
+            match parse_row(
+                i + 1,
+                fields[0],
+                fields[1],
+                fields.get(2).copied(),
+                timelock,
+                raw_units,
+                network,
+            ) {
+                Ok(row) => rows.push(row),
+                Err(err) => errors.push(format!("row {}: {}", i + 1, err)),
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(HeadlessCliError::InvalidInput(errors.join("\n")));
+    }
+    if rows.is_empty() {
+        return Err(HeadlessCliError::InvalidInput(
+            "the payout file has no rows".to_string(),
+        ));
+    }
+    Ok(rows)
+}
+
+/// Group the rows into transactions, leaving room for one change output per token.
+///
+/// # Arguments
+///
+/// * `rows` - rows to group, in order
+/// * `max_outputs` - maximum number of outputs of a transaction
+///
+/// # Examples
+///
+/// ```
+/// use headless_cli::batch::{group_rows, parse_rows};
+///
+/// let content = "WewDeXWyvHP7jJTs7tjLoQfoB72LLxJQqN,1.00\n".repeat(5);
+/// let rows = parse_rows(&content, false, false, None).unwrap();
+/// let groups = group_rows(&rows.iter().collect::<Vec<_>>(), 3);
+/// // 2 payouts and the change on each transaction
+/// assert_eq!(groups.iter().map(|group| group.len()).collect::<Vec<_>>(), vec![2, 2, 1]);
+/// ```
+pub fn group_rows<'a>(rows: &[&'a BatchRow], max_outputs: usize) -> Vec<Vec<&'a BatchRow>> {
+    let mut groups: Vec<Vec<&BatchRow>> = vec![];
+    let mut group: Vec<&BatchRow> = vec![];
+    let mut tokens: HashSet<&str> = HashSet::new();
+
+    for row in rows.iter() {
+        let new_token = !tokens.contains(row.token.as_str());
+        let outputs = group.len() + 1 + tokens.len() + usize::from(new_token);
+        if !group.is_empty() && outputs > max_outputs {
+            groups.push(std::mem::take(&mut group));
+            tokens.clear();
+        }
+        tokens.insert(row.token.as_str());
+        group.push(row);
+    }
+    if !group.is_empty() {
+        groups.push(group);
+    }
+    groups
+}
+
+/// The results file, holding the status of every row.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BatchResults {
+    /// File the payouts were read from
+    pub source: PathBuf,
+    pub rows: Vec<BatchRow>,
+}
+
+impl BatchResults {
+    /// Load the results of a previous run or start new results for these rows.
+    ///
+    /// Fails if the previous results do not match the rows (the payout file
+    /// changed) or have rows with an unknown outcome, unless `retry_unknown`.
+    pub fn load_or_new(
+        path: &Path,
+        source: &Path,
+        rows: Vec<BatchRow>,
+        retry_unknown: bool,
+    ) -> Result<Self, HeadlessCliError> {
+        if !path.exists() {
+            return Ok(BatchResults {
+                source: source.to_path_buf(),
+                rows,
+            });
+        }
+
+        let mut results: BatchResults = read_json_file(path, "a results file")?;
+
+        let matches = results.rows.len() == rows.len()
+            && results
+                .rows
+                .iter()
+                .zip(rows.iter())
+                .all(|(old, new)| old.same_payout(new));
+        if !matches {
+            return Err(HeadlessCliError::InvalidInput(format!(
+                "{} does not match the payout file, use another results file",
+                path.display()
+            )));
+        }
+
+        let unknown: Vec<String> = results
+            .rows
+            .iter()
+            .filter(|row| row.status == RowStatus::Submitting)
+            .map(|row| row.row.to_string())
+            .collect();
+        if !unknown.is_empty() {
+            if !retry_unknown {
+                return Err(HeadlessCliError::InvalidInput(format!(
+                    "rows {} may have been sent by the interrupted run, check the wallet history \
+                     and run again with --retry-unknown to send them again",
+                    unknown.join(", ")
+                )));
+            }
+            for row in results.rows.iter_mut() {
+                if row.status == RowStatus::Submitting {
+                    row.status = RowStatus::Pending;
+                }
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Write the results, replacing the file atomically.
+    pub fn save(&self, path: &Path) -> Result<(), HeadlessCliError> {
+        write_json_file(path, self)
+    }
+
+    fn set_status(
+        &mut self,
+        rows: &[usize],
+        status: RowStatus,
+        tx_id: Option<&str>,
+        error: Option<&str>,
+    ) {
+        for row in self.rows.iter_mut().filter(|row| rows.contains(&row.row)) {
+            row.status = status;
+            row.tx_id = tx_id.map(str::to_string);
+            row.error = error.map(str::to_string);
+        }
+    }
+
+    fn count(&self, status: RowStatus) -> usize {
+        self.rows.iter().filter(|row| row.status == status).count()
+    }
+}
+
+/// Outcome of a batch run.
+#[derive(Serialize, Debug)]
+pub struct BatchSummary {
+    pub sent: usize,
+    pub failed: usize,
+    pub pending: usize,
+    /// Transactions sent on this run
+    pub transactions: Vec<String>,
+    pub results: PathBuf,
+}
+
+/// Send every pending or failed row, saving the results after each step.
+///
+/// A row is marked `submitting` before its transaction is sent, if the
+/// outcome is unknown (connection error or a status other than 400 or 422) the run stops
+/// and the row is kept as `submitting`, so it is not paid twice by accident.
+/// For the same reason the sends are never retried, even with `retry_mutating`.
+/// On a dry run the results are not saved and the first transaction is
+/// returned as [`HeadlessCliError::DryRun`].
+///
+/// # Arguments
+///
+/// * `client` - client used to call the headless
+/// * `wallet_id` - wallet paying the rows
+/// * `results` - rows to send and their status
+/// * `results_path` - where to save the results
+/// * `max_outputs` - maximum number of outputs of a transaction
+/// * `change_address` - send the change of every transaction here
+/// * `on_tx` - called with the rows of each transaction and its response
+///
+pub async fn send_batch<F: FnMut(&[usize], &Result<SendTxResponse, HeadlessCliError>)>(
+    client: &HeadlessClient,
+    wallet_id: &str,
+    results: &mut BatchResults,
+    results_path: &Path,
+    max_outputs: usize,
+    change_address: Option<&str>,
+    mut on_tx: F,
+) -> Result<BatchSummary, HeadlessCliError> {
+    let client = &client.without_mutating_retries();
+    let to_send: Vec<BatchRow> = results
+        .rows
+        .iter()
+        .filter(|row| matches!(row.status, RowStatus::Pending | RowStatus::Failed))
+        .cloned()
+        .collect();
+    let groups = group_rows(&to_send.iter().collect::<Vec<_>>(), max_outputs);
+    let mut transactions = vec![];
+
+    for group in groups {
+        let row_ids: Vec<usize> = group.iter().map(|row| row.row).collect();
+        let outputs: Vec<SendOutput> = group.iter().map(|row| row.to_output()).collect();
+        let params = ParamsWalletSend {
+            wallet_id: wallet_id.to_string(),
+            body: build_send_tx_body(&outputs, &[], change_address)?,
+        };
+
+        if !client.config().dry_run {
+            results.set_status(&row_ids, RowStatus::Submitting, None, None);
+            results.save(results_path)?;
+        }
+
+        let response = client.send_tx(&params).await;
+        on_tx(&row_ids, &response);
+
+        match response {
+            Ok(response) => {
+                results.set_status(&row_ids, RowStatus::Sent, Some(&response.hash), None);
+                transactions.push(response.hash);
+            }
+            // The headless answered and did not send the transaction. Other
+            // statuses (e.g. 408 or 429 from a proxy) do not prove it
+            Err(
+                err @ (HeadlessCliError::Headless { .. }
+                | HeadlessCliError::HttpStatus {
+                    status: 400 | 422, ..
+                }),
+            ) => {
+                results.set_status(&row_ids, RowStatus::Failed, None, Some(&err.to_string()));
+            }
+            // Unknown outcome, keep the rows as submitting and stop
+            Err(err) => return Err(err),
+        }
+        results.save(results_path)?;
+    }
+
+    Ok(BatchSummary {
+        sent: results.count(RowStatus::Sent),
+        failed: results.count(RowStatus::Failed),
+        pending: results.count(RowStatus::Pending),
+        transactions,
+        results: results_path.to_path_buf(),
+    })
+}
+
+/// Default results file of a payout file: `payouts.csv` -> `payouts.csv.results.json`.
+pub fn default_results_path(source: &Path) -> PathBuf {
+    let mut path = source.as_os_str().to_owned();
+    path.push(".results.json");
+    PathBuf::from(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::CliConfig;
+    use crate::utils::tests::{stub_server, TempFile};
+
+    use std::fs;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    const ADDRESS: &str = "WewDeXWyvHP7jJTs7tjLoQfoB72LLxJQqN";
+    const OTHER_ADDRESS: &str = "WmtWgtk5GxdcDKwjNwmXXn74nQWTPWhKfx";
+    const SENT: &str = r#"{"success": true, "hash": "00aa"}"#;
+
+    fn rows(content: &str) -> Vec<BatchRow> {
+        parse_rows(content, false, false, None).unwrap()
+    }
+
+    /// A client of a stub headless that retries mutating requests, with the
+    /// bodies of the requests it received.
+    async fn stub_headless(
+        responses: Vec<(u16, &'static str)>,
+    ) -> (HeadlessClient, Arc<Mutex<Vec<String>>>) {
+        let server = stub_server(responses).await;
+        let config = CliConfig {
+            host: server.host,
+            timeout: Some(Duration::from_secs(5)),
+            retries: 3,
+            retry_backoff: Duration::from_millis(1),
+            retry_mutating: true,
+            ..Default::default()
+        };
+        (HeadlessClient::new(config).unwrap(), server.requests)
+    }
+
+    async fn run(
+        client: &HeadlessClient,
+        results: &mut BatchResults,
+        path: &Path,
+        max_outputs: usize,
+    ) -> Result<BatchSummary, HeadlessCliError> {
+        send_batch(client, "w", results, path, max_outputs, None, |_, _| {}).await
+    }
+
+    fn payouts() -> Vec<BatchRow> {
+        rows(&format!("{},1.00\n{},2.00\n", ADDRESS, OTHER_ADDRESS))
+    }
+
+    fn statuses(results: &BatchResults) -> Vec<RowStatus> {
+        results.rows.iter().map(|row| row.status).collect()
+    }
+
+    fn saved(path: &Path) -> BatchResults {
+        serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn load_or_new_starts_new_results() {
+        let file = TempFile::new("batch_new.json");
+        let results = BatchResults::load_or_new(&file.0, Path::new("p.csv"), payouts(), false);
+        let results = results.unwrap();
+        assert_eq!(results.source, Path::new("p.csv"));
+        assert_eq!(statuses(&results), vec![RowStatus::Pending; 2]);
+    }
+
+    #[test]
+    fn load_or_new_resumes_matching_results() {
+        let file = TempFile::new("batch_resume.json");
+        let mut previous =
+            BatchResults::load_or_new(&file.0, Path::new("p.csv"), payouts(), false).unwrap();
+        previous.set_status(&[1], RowStatus::Sent, Some("00aa"), None);
+        previous.set_status(&[2], RowStatus::Failed, None, Some("rejected"));
+        previous.save(&file.0).unwrap();
+
+        let results =
+            BatchResults::load_or_new(&file.0, Path::new("p.csv"), payouts(), false).unwrap();
+        assert_eq!(statuses(&results), vec![RowStatus::Sent, RowStatus::Failed]);
+        assert_eq!(results.rows[0].tx_id.as_deref(), Some("00aa"));
+    }
+
+    #[test]
+    fn load_or_new_rejects_a_changed_payout_file() {
+        let file = TempFile::new("batch_changed.json");
+        BatchResults::load_or_new(&file.0, Path::new("p.csv"), payouts(), false)
+            .unwrap()
+            .save(&file.0)
+            .unwrap();
+
+        let changed = [
+            rows(&format!("{},1.00\n{},2.01\n", ADDRESS, OTHER_ADDRESS)),
+            rows(&format!("{},1.00\n", ADDRESS)),
+            rows(&format!("{},2.00\n{},1.00\n", OTHER_ADDRESS, ADDRESS)),
+        ];
+        for rows in changed {
+            let err = BatchResults::load_or_new(&file.0, Path::new("p.csv"), rows, false);
+            assert!(err
+                .unwrap_err()
+                .to_string()
+                .contains("does not match the payout file"));
+        }
+    }
+
+    #[test]
+    fn load_or_new_rejects_submitting_rows_unless_retry_unknown() {
+        let file = TempFile::new("batch_submitting.json");
+        let mut previous =
+            BatchResults::load_or_new(&file.0, Path::new("p.csv"), payouts(), false).unwrap();
+        previous.set_status(&[1], RowStatus::Sent, Some("00aa"), None);
+        previous.set_status(&[2], RowStatus::Submitting, None, None);
+        previous.save(&file.0).unwrap();
+
+        let err = BatchResults::load_or_new(&file.0, Path::new("p.csv"), payouts(), false);
+        assert!(err
+            .unwrap_err()
+            .to_string()
+            .contains("rows 2 may have been sent"));
+
+        let results =
+            BatchResults::load_or_new(&file.0, Path::new("p.csv"), payouts(), true).unwrap();
+        assert_eq!(
+            statuses(&results),
+            vec![RowStatus::Sent, RowStatus::Pending]
+        );
+    }
+
+    #[tokio::test]
+    async fn send_batch_skips_sent_rows() {
+        let file = TempFile::new("batch_skip.json");
+        let (client, requests) = stub_headless(vec![(200, SENT)]).await;
+        let mut results =
+            BatchResults::load_or_new(&file.0, Path::new("p.csv"), payouts(), false).unwrap();
+        results.set_status(&[1], RowStatus::Sent, Some("00bb"), None);
+
+        let summary = run(&client, &mut results, &file.0, 10).await.unwrap();
+        assert_eq!(summary.sent, 2);
+        assert_eq!(summary.transactions, vec!["00aa"]);
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert!(!requests[0].contains(ADDRESS) && requests[0].contains(OTHER_ADDRESS));
+
+        let saved = saved(&file.0);
+        assert_eq!(statuses(&saved), vec![RowStatus::Sent; 2]);
+        assert_eq!(saved.rows[0].tx_id.as_deref(), Some("00bb"));
+        assert_eq!(saved.rows[1].tx_id.as_deref(), Some("00aa"));
+    }
+
+    #[tokio::test]
+    async fn send_batch_marks_rejected_rows_failed_and_goes_on() {
+        let file = TempFile::new("batch_failed.json");
+        let rejected = r#"{"success": false, "error": "Insufficient amount of tokens"}"#;
+        let (client, _) = stub_headless(vec![(200, rejected), (200, SENT)]).await;
+        let mut results =
+            BatchResults::load_or_new(&file.0, Path::new("p.csv"), payouts(), false).unwrap();
+
+        // one payout and the change on each transaction
+        let summary = run(&client, &mut results, &file.0, 2).await.unwrap();
+        assert_eq!((summary.sent, summary.failed), (1, 1));
+
+        let saved = saved(&file.0);
+        assert_eq!(statuses(&saved), vec![RowStatus::Failed, RowStatus::Sent]);
+        assert!(saved.rows[0]
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("Insufficient amount"));
+
+        // failed rows are sent again on the next run
+        let (client, requests) = stub_headless(vec![(200, SENT)]).await;
+        let summary = run(&client, &mut results, &file.0, 2).await.unwrap();
+        assert_eq!((summary.sent, summary.failed), (2, 0));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn send_batch_keeps_rows_submitting_without_retrying_unknown_outcomes() {
+        let file = TempFile::new("batch_unknown.json");
+        // the client retries mutating requests, batch sends must not be retried
+        let (client, requests) = stub_headless(vec![(503, "unavailable"), (200, SENT)]).await;
+        assert!(client.config().retry_mutating);
+        let mut results =
+            BatchResults::load_or_new(&file.0, Path::new("p.csv"), payouts(), false).unwrap();
+
+        let err = run(&client, &mut results, &file.0, 10).await.unwrap_err();
+        assert!(matches!(
+            err,
+            HeadlessCliError::HttpStatus { status: 503, .. }
+        ));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+        assert_eq!(statuses(&saved(&file.0)), vec![RowStatus::Submitting; 2]);
+
+        let err = BatchResults::load_or_new(&file.0, Path::new("p.csv"), payouts(), false);
+        assert!(err.is_err());
+    }
+
+    #[tokio::test]
+    async fn send_batch_marks_rows_failed_only_on_rejections() {
+        for status in [400, 422] {
+            let file = TempFile::new(&format!("batch_rejected_{}.json", status));
+            let (client, _) = stub_headless(vec![(status, r#"{"error": "invalid"}"#)]).await;
+            let mut results =
+                BatchResults::load_or_new(&file.0, Path::new("p.csv"), payouts(), false).unwrap();
+            let summary = run(&client, &mut results, &file.0, 10).await.unwrap();
+            assert_eq!(summary.failed, 2, "status {}", status);
+        }
+
+        // a proxy may answer these after the headless got the transaction
+        for status in [408, 429, 500] {
+            let file = TempFile::new(&format!("batch_unknown_{}.json", status));
+            let (client, _) = stub_headless(vec![(status, "")]).await;
+            let mut results =
+                BatchResults::load_or_new(&file.0, Path::new("p.csv"), payouts(), false).unwrap();
+            assert!(run(&client, &mut results, &file.0, 10).await.is_err());
+            assert_eq!(
+                statuses(&saved(&file.0)),
+                vec![RowStatus::Submitting; 2],
+                "status {}",
+                status
+            );
+        }
+    }
+}
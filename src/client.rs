@@ -0,0 +1,1506 @@
+use crate::data::*;
+use crate::error::HeadlessCliError;
+use crate::params::*;
+use crate::send::{check_funds, required_by_token, tx_proposal_body};
+use crate::utils::*;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use std::time::{Duration, Instant};
+
+use log::{debug, warn};
+use reqwest::{Method, Request, RequestBuilder};
+use serde::de::DeserializeOwned;
+
+/////////////////////////////////////////// Client
+
+/// Upper bound of the delay between retries.
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(30);
+
+/// First and maximum delay between polls when waiting for a condition.
+const MIN_POLL_DELAY: Duration = Duration::from_millis(500);
+const MAX_POLL_DELAY: Duration = Duration::from_secs(5);
+
+/// Connection errors (including timeouts) and gateway errors are transient.
+fn is_retryable(err: &HeadlessCliError) -> bool {
+    match err {
+        HeadlessCliError::Connection(_) => true,
+        HeadlessCliError::HttpStatus { status, .. } => matches!(status, 502..=504),
+        _ => false,
+    }
+}
+
+/// A client for the headless wallet API.
+///
+/// All requests share the same underlying http client, so building a single
+/// `HeadlessClient` and reusing it for every call is preferred.
+///
+/// # Examples
+///
+/// ```no_run
+/// use headless_cli::client::HeadlessClient;
+/// use headless_cli::params::{CliConfig, ParamsWalletBalance};
+///
+/// # async fn run() -> Result<(), headless_cli::error::HeadlessCliError> {
+/// let config = CliConfig {
+///     host: "http://localhost:8000".to_string(),
+///     ..Default::default()
+/// };
+/// let client = HeadlessClient::new(config)?;
+/// let params = ParamsWalletBalance {
+///     wallet_id: "default".to_string(),
+///     token: None,
+/// };
+/// let balance = client.balance(&params).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct HeadlessClient {
+    config: CliConfig,
+    client: reqwest::Client,
+}
+
+impl HeadlessClient {
+    /// Create a new client from the common cli configuration.
+    pub fn new(config: CliConfig) -> Result<Self, HeadlessCliError> {
+        let client = build_client(&config)?;
+        Ok(HeadlessClient { config, client })
+    }
+
+    /// The configuration used to build this client.
+    pub fn config(&self) -> &CliConfig {
+        &self.config
+    }
+
+    /// The same client, never retrying requests that change the wallet even
+    /// with `retry_mutating`, for callers that track if a request was executed.
+    pub fn without_mutating_retries(&self) -> Self {
+        let mut client = self.clone();
+        client.config.retry_mutating = false;
+        client
+    }
+
+    /// Start building a request to the headless.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - http method of the request
+    /// * `path` - The path required to be called
+    /// * `wallet_id` - Optionally send the `X-Wallet-Id` header
+    ///
+    fn request(
+        &self,
+        method: Method,
+        path: &str,
+        wallet_id: Option<&str>,
+    ) -> Result<RequestBuilder, HeadlessCliError> {
+        let url = build_headless_url(&self.config.host, path)?;
+        debug!("{} {}", method, url);
+
+        let mut req_builder = self.client.request(method, url);
+        if let Some(wallet_id) = wallet_id {
+            req_builder = req_builder.header("X-Wallet-Id", wallet_id);
+        }
+        Ok(req_builder)
+    }
+
+    /// Send the request and parse the json response.
+    ///
+    /// Failures reported by the headless (non-2xx status or `success: false`)
+    /// are returned as errors, see [`check_response`].
+    /// Only GET requests are retried, unless `retry_mutating` is configured.
+    /// Other requests that time out after being sent fail with
+    /// [`HeadlessCliError::Timeout`], as they may have been executed.
+    async fn send<T: DeserializeOwned>(
+        &self,
+        req_builder: RequestBuilder,
+    ) -> Result<T, HeadlessCliError> {
+        let request = req_builder.build()?;
+        let idempotent = request.method() == Method::GET;
+        self.execute(request, idempotent).await
+    }
+
+    /// Same as [`HeadlessClient::send`] for POST requests that do not change
+    /// the wallet, so they are retried like a GET.
+    async fn send_idempotent<T: DeserializeOwned>(
+        &self,
+        req_builder: RequestBuilder,
+    ) -> Result<T, HeadlessCliError> {
+        let request = req_builder.build()?;
+        self.execute(request, true).await
+    }
+
+    async fn execute<T: DeserializeOwned>(
+        &self,
+        request: Request,
+        idempotent: bool,
+    ) -> Result<T, HeadlessCliError> {
+        if self.config.dry_run && !idempotent {
+            let request = self.dry_run_request(&request)?;
+            return Err(HeadlessCliError::DryRun(Box::new(request)));
+        }
+
+        let retries = if idempotent || self.config.retry_mutating {
+            self.config.retries
+        } else {
+            0
+        };
+        let mut backoff = self.config.retry_backoff;
+        let mut attempt = 0;
+        let target = format!("{} {}", request.method(), request.url());
+
+        let result = loop {
+            // Json bodies can always be cloned, the last attempt consumes the request
+            let current = match request.try_clone() {
+                Some(current) if attempt < retries => current,
+                _ => break self.execute_once(request).await,
+            };
+
+            match self.execute_once(current).await {
+                Err(err) if is_retryable(&err) => {
+                    attempt += 1;
+                    warn!("{} (retry {}/{} in {:?})", err, attempt, retries, backoff);
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_RETRY_BACKOFF);
+                }
+                result => break result,
+            }
+        };
+
+        let text_response = result.map_err(|err| match err {
+            // The request was sent, the headless may still execute it
+            HeadlessCliError::Connection(err)
+                if !idempotent && err.is_timeout() && !err.is_connect() =>
+            {
+                HeadlessCliError::Timeout(format!(
+                    "no response to {} in time, its outcome is unknown: check the wallet \
+                     history before sending it again",
+                    target
+                ))
+            }
+            err => err,
+        })?;
+
+        Ok(serde_json::from_str::<T>(&text_response)?)
+    }
+
+    /// Describe a request that will not be sent, credentials are redacted.
+    fn dry_run_request(&self, request: &Request) -> Result<DryRunRequest, HeadlessCliError> {
+        // The profile headers may hold credentials too (e.g. a gateway key)
+        let mut headers = BTreeMap::new();
+        for (name, _) in self.config.request_headers()? {
+            headers.insert(name, "<redacted>".to_string());
+        }
+        for (name, value) in request.headers().iter() {
+            let value = value.to_str().unwrap_or("<binary>");
+            headers.insert(name.to_string(), value.to_string());
+        }
+
+        let body = request.body().and_then(|body| body.as_bytes()).map(|body| {
+            serde_json::from_slice(body).unwrap_or_else(|_| {
+                serde_json::Value::String(String::from_utf8_lossy(body).to_string())
+            })
+        });
+
+        Ok(DryRunRequest {
+            method: request.method().to_string(),
+            url: request.url().to_string(),
+            headers,
+            body,
+            preview: None,
+        })
+    }
+
+    /// Send the request once and return the body of a successful response.
+    async fn execute_once(&self, request: Request) -> Result<String, HeadlessCliError> {
+        let response = self.client.execute(request).await?;
+        let status = response.status();
+        let text_response = response.text().await?;
+        debug!("{} {}", status, text_response);
+
+        check_response(status, &text_response)?;
+        Ok(text_response)
+    }
+
+    /// Start a wallet
+    pub async fn start(&self, params: &ParamsStart) -> Result<SuccessResponse, HeadlessCliError> {
+        let mut map: HashMap<&str, HashMapValue> = HashMap::new();
+        map.insert("seedKey", params.seed_key.clone().into());
+        map.insert("wallet-id", params.wallet_id.clone().into());
+
+        if let Some(passphrase) = &params.passphrase {
+            map.insert("passphrase", passphrase.clone().into());
+        }
+
+        if let Some(scan_policy) = &params.scan_policy {
+            map.insert("scanPolicy", scan_policy.clone().into());
+        }
+
+        if let Some(gap_limit) = params.gap_limit {
+            map.insert("gapLimit", gap_limit.into());
+        }
+
+        if let Some(policy_start_index) = params.policy_start_index {
+            map.insert("policyStartIndex", policy_start_index.into());
+        }
+
+        if let Some(policy_end_index) = params.policy_end_index {
+            map.insert("policyEndIndex", policy_end_index.into());
+        }
+
+        if let Some(history_sync_mode) = &params.history_sync_mode {
+            map.insert("history_sync_mode", history_sync_mode.clone().into());
+        }
+
+        if params.multisig {
+            map.insert("multisig", true.into());
+
+            if let Some(multisig_key) = &params.multisig_key {
+                map.insert("multisigKey", multisig_key.clone().into());
+            }
+        }
+
+        let req_builder = self.request(Method::POST, "/start", None)?.json(&map);
+        self.send(req_builder).await
+    }
+
+    /// Start an HSM wallet
+    pub async fn hsm_start(
+        &self,
+        params: &ParamsHsmStart,
+    ) -> Result<SuccessResponse, HeadlessCliError> {
+        let mut map = HashMap::new();
+        map.insert("hsm-key", params.hsm_key.clone());
+        map.insert("wallet-id", params.wallet_id.clone());
+
+        let req_builder = self.request(Method::POST, "/hsm/start", None)?.json(&map);
+        self.send(req_builder).await
+    }
+
+    /// Start a Fireblocks wallet
+    pub async fn fireblocks_start(
+        &self,
+        params: &ParamsFireblocksStart,
+    ) -> Result<SuccessResponse, HeadlessCliError> {
+        let mut map = HashMap::new();
+        map.insert("xpub", params.xpub.clone());
+        map.insert("wallet-id", params.wallet_id.clone());
+
+        let req_builder = self
+            .request(Method::POST, "/fireblocks/start", None)?
+            .json(&map);
+        self.send(req_builder).await
+    }
+
+    /// Get the configuration string of a token
+    pub async fn configuration_string(
+        &self,
+        params: &ParamsConfigString,
+    ) -> Result<ConfigurationStringResponse, HeadlessCliError> {
+        let req_builder = self
+            .request(Method::GET, "/configuration-string", None)?
+            .query(&[("token", &params.token)]);
+        self.send(req_builder).await
+    }
+
+    /// Symbol of a token, from its configuration string `[name:symbol:uid:checksum]`
+    pub async fn token_symbol(&self, token: &str) -> Result<String, HeadlessCliError> {
+        let params = ParamsConfigString {
+            token: token.to_string(),
+        };
+        let response = self.configuration_string(&params).await?;
+        response
+            .configuration_string
+            .split(':')
+            .nth(1)
+            .map(str::to_string)
+            .ok_or_else(|| HeadlessCliError::Headless {
+                message: format!("invalid configuration string of token {}", token),
+                body: response.configuration_string.clone(),
+            })
+    }
+
+    /// Get the multisig xpubkey of the configured seed
+    pub async fn multisig_pubkey(
+        &self,
+        params: &ParamsMultisigPubkey,
+    ) -> Result<MultisigPubkeyResponse, HeadlessCliError> {
+        let mut map = HashMap::new();
+        map.insert("seedKey", params.seed_key.clone());
+
+        if let Some(passphrase) = &params.passphrase {
+            map.insert("passphrase", passphrase.clone());
+        }
+
+        let req_builder = self
+            .request(Method::POST, "/multisig-pubkey", None)?
+            .json(&map);
+        self.send(req_builder).await
+    }
+
+    /// Get the status of a wallet
+    pub async fn status(&self, wallet_id: &str) -> Result<StatusResponse, HeadlessCliError> {
+        let req_builder = self.request(Method::GET, "/wallet/status", Some(wallet_id))?;
+        self.send(req_builder).await
+    }
+
+    /// Poll the wallet status until it is ready, with backoff between polls.
+    ///
+    /// `on_status` is called with every status received so the caller can
+    /// show the sync progress.
+    ///
+    /// # Arguments
+    ///
+    /// * `wallet_id` - which wallet to wait for
+    /// * `timeout` - give up with [`HeadlessCliError::Timeout`] after this long
+    /// * `on_status` - called with every status polled
+    ///
+    pub async fn wait_ready<F: FnMut(&StatusResponse)>(
+        &self,
+        wallet_id: &str,
+        timeout: Duration,
+        mut on_status: F,
+    ) -> Result<StatusResponse, HeadlessCliError> {
+        let deadline = Instant::now() + timeout;
+        let mut delay = MIN_POLL_DELAY;
+
+        loop {
+            let status = self.status(wallet_id).await?;
+            on_status(&status);
+
+            if status.is_ready() {
+                return Ok(status);
+            }
+            if status.is_error() {
+                return Err(HeadlessCliError::Headless {
+                    message: format!("wallet {} failed: {}", wallet_id, status.status_message),
+                    body: serde_json::to_string(&status)?,
+                });
+            }
+            if Instant::now() + delay > deadline {
+                return Err(HeadlessCliError::Timeout(format!(
+                    "wallet {} is not ready after {}s ({})",
+                    wallet_id,
+                    timeout.as_secs(),
+                    status.status_message
+                )));
+            }
+
+            tokio::time::sleep(delay).await;
+            delay = (delay * 3 / 2).min(MAX_POLL_DELAY);
+        }
+    }
+
+    /// Get balance of a token in the given wallet
+    pub async fn balance(
+        &self,
+        params: &ParamsWalletBalance,
+    ) -> Result<BalanceResponse, HeadlessCliError> {
+        let mut req_builder =
+            self.request(Method::GET, "/wallet/balance", Some(&params.wallet_id))?;
+
+        if let Some(token) = &params.token {
+            req_builder = req_builder.query(&[("token", token)]);
+        }
+
+        self.send(req_builder).await
+    }
+
+    /// Get current address from the given wallet
+    pub async fn address(
+        &self,
+        params: &ParamsWalletAddress,
+    ) -> Result<AddressResponse, HeadlessCliError> {
+        let mut req_builder =
+            self.request(Method::GET, "/wallet/address", Some(&params.wallet_id))?;
+
+        if let Some(index) = params.index {
+            req_builder = req_builder.query(&[("index", index)]);
+        }
+
+        if let Some(mark_as_used) = params.mark_as_used {
+            req_builder = req_builder.query(&[("mark_as_used", mark_as_used)]);
+        }
+
+        self.send(req_builder).await
+    }
+
+    /// Get the address info if the address belongs to the given wallet
+    pub async fn address_info(
+        &self,
+        params: &ParamsWalletAddressInfo,
+    ) -> Result<AddressInfoResponse, HeadlessCliError> {
+        let mut req_builder = self
+            .request(Method::GET, "/wallet/address-info", Some(&params.wallet_id))?
+            .query(&[("address", &params.address)]);
+
+        if let Some(token) = &params.token {
+            req_builder = req_builder.query(&[("token", token)]);
+        }
+
+        self.send(req_builder).await
+    }
+
+    /// Check if the address belongs to the given wallet
+    pub async fn is_address_mine(
+        &self,
+        wallet_id: &str,
+        address: &str,
+    ) -> Result<bool, HeadlessCliError> {
+        let params = ParamsWalletAddressInfo {
+            wallet_id: wallet_id.to_string(),
+            address: address.to_string(),
+            token: None,
+        };
+        match self.address_info(&params).await {
+            Ok(response) => Ok(response.success),
+            // The headless fails the call if the address is not from the wallet
+            Err(HeadlessCliError::Headless { .. }) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Get the address index if the address belongs to the given wallet
+    pub async fn address_index(
+        &self,
+        params: &ParamsWalletAddressIndex,
+    ) -> Result<AddressIndexResponse, HeadlessCliError> {
+        let req_builder = self
+            .request(
+                Method::GET,
+                "/wallet/address-index",
+                Some(&params.wallet_id),
+            )?
+            .query(&[("address", &params.address)]);
+        self.send(req_builder).await
+    }
+
+    /// Get all addresses from the given wallet
+    pub async fn addresses(
+        &self,
+        params: &ParamsWalletAddresses,
+    ) -> Result<AddressesResponse, HeadlessCliError> {
+        let req_builder =
+            self.request(Method::GET, "/wallet/addresses", Some(&params.wallet_id))?;
+        self.send(req_builder).await
+    }
+
+    /// Get the transaction history of the given wallet
+    pub async fn tx_history(
+        &self,
+        params: &ParamsWalletTxHistory,
+    ) -> Result<Vec<HistoryTx>, HeadlessCliError> {
+        let mut req_builder =
+            self.request(Method::GET, "/wallet/tx-history", Some(&params.wallet_id))?;
+
+        if let Some(limit) = params.limit {
+            req_builder = req_builder.query(&[("limit", limit)]);
+        }
+
+        self.send(req_builder).await
+    }
+
+    /// Get the transaction details from the tx_id if the transaction belongs to the wallet.
+    pub async fn transaction(
+        &self,
+        params: &ParamsWalletTransaction,
+    ) -> Result<HistoryTx, HeadlessCliError> {
+        let req_builder = self
+            .request(Method::GET, "/wallet/transaction", Some(&params.wallet_id))?
+            .query(&[("id", &params.id)]);
+        self.send(req_builder).await
+    }
+
+    /// Decode the given transaction.
+    pub async fn decode(
+        &self,
+        params: &ParamsWalletDecode,
+    ) -> Result<DecodeResponse, HeadlessCliError> {
+        if params.tx_hex.is_none() && params.partial_tx.is_none() {
+            return Err(HeadlessCliError::InvalidInput(
+                "either tx_hex or partial_tx is required".to_string(),
+            ));
+        }
+
+        let mut map = HashMap::new();
+
+        if let Some(tx_hex) = &params.tx_hex {
+            map.insert("txHex", tx_hex.clone());
+        }
+
+        if let Some(partial_tx) = &params.partial_tx {
+            map.insert("partial_tx", partial_tx.clone());
+        }
+
+        let req_builder = self
+            .request(Method::POST, "/wallet/decode", Some(&params.wallet_id))?
+            .json(&map);
+        self.send_idempotent(req_builder).await
+    }
+
+    /// Get the number of blocks confirming a given transaction.
+    pub async fn tx_confirmation(
+        &self,
+        params: &ParamsWalletTxConfirmation,
+    ) -> Result<TxConfirmationResponse, HeadlessCliError> {
+        let req_builder = self
+            .request(
+                Method::GET,
+                "/wallet/tx-confirmation-blocks",
+                Some(&params.wallet_id),
+            )?
+            .query(&[("id", &params.id)]);
+        self.send(req_builder).await
+    }
+
+    /// Poll the confirmations of a transaction until it reaches the given number,
+    /// failing with [`HeadlessCliError::Voided`] if the transaction is voided.
+    ///
+    /// # Arguments
+    ///
+    /// * `wallet_id` - wallet that sent or received the transaction
+    /// * `tx_id` - transaction to wait for
+    /// * `params` - how many confirmations and for how long to wait
+    /// * `on_confirmation` - called with the confirmation number of every poll
+    ///
+    pub async fn wait_confirmations<F: FnMut(u64)>(
+        &self,
+        wallet_id: &str,
+        tx_id: &str,
+        params: &ParamsWaitConfirmations,
+        mut on_confirmation: F,
+    ) -> Result<u64, HeadlessCliError> {
+        let deadline = Instant::now() + params.timeout;
+        let mut delay = MIN_POLL_DELAY;
+
+        let tx_params = ParamsWalletTransaction {
+            wallet_id: wallet_id.to_string(),
+            id: tx_id.to_string(),
+        };
+        let confirmation_params = ParamsWalletTxConfirmation {
+            wallet_id: wallet_id.to_string(),
+            id: tx_id.to_string(),
+        };
+
+        loop {
+            if self.transaction(&tx_params).await?.is_voided {
+                return Err(HeadlessCliError::Voided(tx_id.to_string()));
+            }
+
+            let confirmations = self
+                .tx_confirmation(&confirmation_params)
+                .await?
+                .confirmation_number;
+            on_confirmation(confirmations);
+
+            if confirmations >= params.confirmations {
+                return Ok(confirmations);
+            }
+            if Instant::now() + delay > deadline {
+                return Err(HeadlessCliError::Timeout(format!(
+                    "transaction {} has {} of {} confirmations after {}s",
+                    tx_id,
+                    confirmations,
+                    params.confirmations,
+                    params.timeout.as_secs()
+                )));
+            }
+
+            tokio::time::sleep(delay).await;
+            delay = (delay * 3 / 2).min(MAX_POLL_DELAY);
+        }
+    }
+
+    /// Send a simple transaction.
+    pub async fn simple_send(
+        &self,
+        params: &ParamsWalletSimpleSend,
+    ) -> Result<SendTxResponse, HeadlessCliError> {
+        let mut map: HashMap<&str, HashMapValue> = HashMap::new();
+        map.insert("address", params.address.clone().into());
+        map.insert("value", params.value.into());
+
+        if let Some(change_address) = &params.change_address {
+            map.insert("change_address", change_address.clone().into());
+        }
+
+        if let Some(token) = &params.token {
+            map.insert("token", token.clone().into());
+        }
+
+        let req_builder = self
+            .request(
+                Method::POST,
+                "/wallet/simple-send-tx",
+                Some(&params.wallet_id),
+            )?
+            .json(&map);
+        self.send(req_builder).await
+    }
+
+    /// Send a transaction as specified in the given body.
+    pub async fn send_tx(
+        &self,
+        params: &ParamsWalletSend,
+    ) -> Result<SendTxResponse, HeadlessCliError> {
+        let req_builder = self
+            .request(Method::POST, "/wallet/send-tx", Some(&params.wallet_id))?
+            .header("Content-Type", "application/json")
+            .body(params.body.clone());
+        self.send(req_builder).await
+    }
+
+    /// Create a custom token in the given wallet.
+    pub async fn create_token(
+        &self,
+        params: &ParamsWalletCreateToken,
+    ) -> Result<SendTxResponse, HeadlessCliError> {
+        let mut map: HashMap<&str, HashMapValue> = HashMap::new();
+        map.insert("name", params.name.clone().into());
+        map.insert("symbol", params.symbol.clone().into());
+        map.insert("amount", params.amount.into());
+
+        if let Some(address) = &params.address {
+            map.insert("address", address.clone().into());
+        }
+
+        if let Some(change_address) = &params.change_address {
+            map.insert("change_address", change_address.clone().into());
+        }
+
+        if let Some(create_mint) = params.create_mint {
+            map.insert("create_mint", create_mint.into());
+        }
+
+        if let Some(mint_authority_address) = &params.mint_authority_address {
+            map.insert(
+                "mint_authority_address",
+                mint_authority_address.clone().into(),
+            );
+        }
+
+        if let Some(allow_external_mint_authority_address) =
+            params.allow_external_mint_authority_address
+        {
+            map.insert(
+                "allow_external_mint_authority_address",
+                allow_external_mint_authority_address.into(),
+            );
+        }
+
+        if let Some(create_melt) = params.create_melt {
+            map.insert("create_melt", create_melt.into());
+        }
+
+        if let Some(melt_authority_address) = &params.melt_authority_address {
+            map.insert(
+                "melt_authority_address",
+                melt_authority_address.clone().into(),
+            );
+        }
+
+        if let Some(allow_external_melt_authority_address) =
+            params.allow_external_melt_authority_address
+        {
+            map.insert(
+                "allow_external_melt_authority_address",
+                allow_external_melt_authority_address.into(),
+            );
+        }
+
+        if let Some(data) = &params.data {
+            map.insert(
+                "data",
+                data.iter()
+                    .map(|s| s.clone().into())
+                    .collect::<Vec<HashMapValue>>()
+                    .into(),
+            );
+        }
+
+        let req_builder = self
+            .request(
+                Method::POST,
+                "/wallet/create-token",
+                Some(&params.wallet_id),
+            )?
+            .json(&map);
+        self.send(req_builder).await
+    }
+
+    /// Mint tokens.
+    pub async fn mint_tokens(
+        &self,
+        params: &ParamsWalletMintTokens,
+    ) -> Result<SendTxResponse, HeadlessCliError> {
+        let mut map: HashMap<&str, HashMapValue> = HashMap::new();
+        map.insert("token", params.token.clone().into());
+        map.insert("amount", params.amount.into());
+
+        if let Some(address) = &params.address {
+            map.insert("address", address.clone().into());
+        }
+
+        if let Some(change_address) = &params.change_address {
+            map.insert("change_address", change_address.clone().into());
+        }
+
+        if let Some(mint_authority_address) = &params.mint_authority_address {
+            map.insert(
+                "mint_authority_address",
+                mint_authority_address.clone().into(),
+            );
+        }
+
+        if let Some(allow_external_mint_authority_address) =
+            params.allow_external_mint_authority_address
+        {
+            map.insert(
+                "allow_external_mint_authority_address",
+                allow_external_mint_authority_address.into(),
+            );
+        }
+
+        if let Some(unshift_data) = params.unshift_data {
+            map.insert("unshift_data", unshift_data.into());
+        }
+
+        if let Some(data) = &params.data {
+            map.insert(
+                "data",
+                data.iter()
+                    .map(|s| s.clone().into())
+                    .collect::<Vec<HashMapValue>>()
+                    .into(),
+            );
+        }
+
+        let req_builder = self
+            .request(Method::POST, "/wallet/mint-tokens", Some(&params.wallet_id))?
+            .json(&map);
+        self.send(req_builder).await
+    }
+
+    /// Melt tokens
+    pub async fn melt_tokens(
+        &self,
+        params: &ParamsWalletMeltTokens,
+    ) -> Result<SendTxResponse, HeadlessCliError> {
+        let mut map: HashMap<&str, HashMapValue> = HashMap::new();
+        map.insert("token", params.token.clone().into());
+        map.insert("amount", params.amount.into());
+
+        if let Some(address) = &params.address {
+            map.insert("address", address.clone().into());
+        }
+
+        if let Some(deposit_address) = &params.deposit_address {
+            map.insert("deposit_address", deposit_address.clone().into());
+        }
+
+        if let Some(change_address) = &params.change_address {
+            map.insert("change_address", change_address.clone().into());
+        }
+
+        if let Some(melt_authority_address) = &params.melt_authority_address {
+            map.insert(
+                "melt_authority_address",
+                melt_authority_address.clone().into(),
+            );
+        }
+
+        if let Some(allow_external_melt_authority_address) =
+            params.allow_external_melt_authority_address
+        {
+            map.insert(
+                "allow_external_melt_authority_address",
+                allow_external_melt_authority_address.into(),
+            );
+        }
+
+        if let Some(unshift_data) = params.unshift_data {
+            map.insert("unshiftData", unshift_data.into());
+        }
+
+        if let Some(data) = &params.data {
+            map.insert(
+                "data",
+                data.iter()
+                    .map(|s| s.clone().into())
+                    .collect::<Vec<HashMapValue>>()
+                    .into(),
+            );
+        }
+
+        let req_builder = self
+            .request(Method::POST, "/wallet/melt-tokens", Some(&params.wallet_id))?
+            .json(&map);
+        self.send(req_builder).await
+    }
+
+    /// Get utxos following the rules defined in the given filters
+    pub async fn utxo_filter(
+        &self,
+        params: &ParamsWalletUtxoFilter,
+    ) -> Result<UtxoFilterResponse, HeadlessCliError> {
+        let mut map: HashMap<&str, HashMapValue> = HashMap::new();
+
+        if let Some(max_utxos) = params.max_utxos {
+            map.insert("max_utxos", max_utxos.into());
+        }
+
+        if let Some(token) = &params.token {
+            map.insert("token", token.clone().into());
+        }
+
+        if let Some(filter_address) = &params.filter_address {
+            map.insert("filter_address", filter_address.clone().into());
+        }
+
+        if let Some(amount_smaller_than) = params.amount_smaller_than {
+            map.insert("amount_smaller_than", amount_smaller_than.into());
+        }
+
+        if let Some(amount_bigger_than) = params.amount_bigger_than {
+            map.insert("amount_bigger_than", amount_bigger_than.into());
+        }
+
+        if let Some(maximum_amount) = params.maximum_amount {
+            map.insert("maximum_amount", maximum_amount.into());
+        }
+
+        if let Some(only_available_utxos) = params.only_available_utxos {
+            map.insert("only_available_utxos", only_available_utxos.into());
+        }
+
+        let req_builder = self
+            .request(Method::POST, "/wallet/utxo-filter", Some(&params.wallet_id))?
+            .json(&map);
+        self.send_idempotent(req_builder).await
+    }
+
+    /// Consolidate the utxos following the given filters
+    pub async fn utxo_consolidation(
+        &self,
+        params: &ParamsWalletUtxoConsolidation,
+    ) -> Result<UtxoConsolidationResponse, HeadlessCliError> {
+        let mut map: HashMap<&str, HashMapValue> = HashMap::new();
+
+        if let Some(max_utxos) = params.max_utxos {
+            map.insert("max_utxos", max_utxos.into());
+        }
+
+        if let Some(token) = &params.token {
+            map.insert("token", token.clone().into());
+        }
+
+        if let Some(filter_address) = &params.filter_address {
+            map.insert("filter_address", filter_address.clone().into());
+        }
+
+        if let Some(amount_smaller_than) = params.amount_smaller_than {
+            map.insert("amount_smaller_than", amount_smaller_than.into());
+        }
+
+        if let Some(amount_bigger_than) = params.amount_bigger_than {
+            map.insert("amount_bigger_than", amount_bigger_than.into());
+        }
+
+        if let Some(maximum_amount) = params.maximum_amount {
+            map.insert("maximum_amount", maximum_amount.into());
+        }
+
+        let req_builder = self
+            .request(
+                Method::POST,
+                "/wallet/utxo-consolidation",
+                Some(&params.wallet_id),
+            )?
+            .json(&map);
+        self.send(req_builder).await
+    }
+
+    /// Create an NFT in the given wallet.
+    pub async fn create_nft(
+        &self,
+        params: &ParamsWalletCreateNft,
+    ) -> Result<SendTxResponse, HeadlessCliError> {
+        let mut map: HashMap<&str, HashMapValue> = HashMap::new();
+        map.insert("name", params.name.clone().into());
+        map.insert("symbol", params.symbol.clone().into());
+        map.insert("data", params.data.clone().into());
+        map.insert("amount", params.amount.into());
+
+        if let Some(address) = &params.address {
+            map.insert("address", address.clone().into());
+        }
+
+        if let Some(change_address) = &params.change_address {
+            map.insert("change_address", change_address.clone().into());
+        }
+
+        if let Some(create_mint) = params.create_mint {
+            map.insert("create_mint", create_mint.into());
+        }
+
+        if let Some(mint_authority_address) = &params.mint_authority_address {
+            map.insert(
+                "mint_authority_address",
+                mint_authority_address.clone().into(),
+            );
+        }
+
+        if let Some(allow_external_mint_authority_address) =
+            params.allow_external_mint_authority_address
+        {
+            map.insert(
+                "allow_external_mint_authority_address",
+                allow_external_mint_authority_address.into(),
+            );
+        }
+
+        if let Some(create_melt) = params.create_melt {
+            map.insert("create_melt", create_melt.into());
+        }
+
+        if let Some(melt_authority_address) = &params.melt_authority_address {
+            map.insert(
+                "melt_authority_address",
+                melt_authority_address.clone().into(),
+            );
+        }
+
+        if let Some(allow_external_melt_authority_address) =
+            params.allow_external_melt_authority_address
+        {
+            map.insert(
+                "allow_external_melt_authority_address",
+                allow_external_melt_authority_address.into(),
+            );
+        }
+
+        let req_builder = self
+            .request(Method::POST, "/wallet/create-nft", Some(&params.wallet_id))?
+            .json(&map);
+        self.send(req_builder).await
+    }
+
+    /// Stop a wallet.
+    pub async fn stop(
+        &self,
+        params: &ParamsWalletStop,
+    ) -> Result<SuccessResponse, HeadlessCliError> {
+        let req_builder = self.request(Method::POST, "/wallet/stop", Some(&params.wallet_id))?;
+        self.send(req_builder).await
+    }
+
+    /// List all tokens found on the wallet history.
+    ///
+    /// This requires fetching the wallet history and addresses, any token
+    /// sent from or to an address of the wallet is included.
+    pub async fn list_tokens(
+        &self,
+        params: &ParamsCustomListTokens,
+    ) -> Result<HashSet<String>, HeadlessCliError> {
+        let mut tokens = HashSet::new();
+
+        let req_builder =
+            self.request(Method::GET, "/wallet/tx-history", Some(&params.wallet_id))?;
+        let tx_history: Vec<HistoryTx> = self.send(req_builder).await?;
+
+        let req_builder =
+            self.request(Method::GET, "/wallet/addresses", Some(&params.wallet_id))?;
+        let mut addresses = self.send::<AddressesResponse>(req_builder).await?.addresses;
+        let known_addresses: HashSet<String> = addresses.drain(..).collect();
+
+        for tx in tx_history.iter() {
+            // Find tokens in the outputs
+            for output in tx.outputs.iter() {
+                if let Some(address) = output.decoded.address.clone() {
+                    if known_addresses.contains(&address) {
+                        // Address is mine, so the token is mine also
+                        tokens.insert(output.token.clone());
+                    }
+                }
+            }
+
+            for input in tx.inputs.iter() {
+                if let Some(address) = input.decoded.address.clone() {
+                    if known_addresses.contains(&address) {
+                        // Address is mine, so the token is mine also
+                        tokens.insert(input.token.clone());
+                    }
+                }
+            }
+        }
+
+        debug!("Found {} tokens.", tokens.len());
+        Ok(tokens)
+    }
+
+    /// Build a tx proposal from a json body
+    pub async fn p2sh_txproposal_build(
+        &self,
+        params: &ParamsP2shTxProposalBuild,
+    ) -> Result<TxProposalResponse, HeadlessCliError> {
+        let req_builder = self
+            .request(
+                Method::POST,
+                "/wallet/p2sh/tx-proposal",
+                Some(&params.wallet_id),
+            )?
+            .header("Content-Type", "application/json")
+            .body(params.body.clone());
+        self.send(req_builder).await
+    }
+
+    /// Fail early if the wallet balance does not cover the outputs of a `send-tx` body.
+    ///
+    /// The balance of each token on the outputs is fetched, see [`check_funds`].
+    pub async fn check_funds(&self, wallet_id: &str, body: &str) -> Result<(), HeadlessCliError> {
+        let required = required_by_token(body)?;
+        let mut balances = BTreeMap::new();
+        for token in required.keys() {
+            let params = ParamsWalletBalance {
+                wallet_id: wallet_id.to_string(),
+                token: Some(token.clone()),
+            };
+            balances.insert(token.clone(), self.balance(&params).await?);
+        }
+        check_funds(&required, &balances)
+    }
+
+    /// Build the transaction of a `send-tx` body without pushing it and decode it.
+    ///
+    /// Uses the P2SH tx proposal endpoint without marking the inputs as used,
+    /// so it only works for P2SH wallets, see [`tx_proposal_body`].
+    pub async fn preview_send_tx(
+        &self,
+        wallet_id: &str,
+        body: &str,
+    ) -> Result<DecodeResponse, HeadlessCliError> {
+        let mut map = tx_proposal_body(body)?;
+        map.insert("mark_inputs_as_used".to_string(), false.into());
+
+        let req_builder = self
+            .request(Method::POST, "/wallet/p2sh/tx-proposal", Some(wallet_id))?
+            .json(&map);
+        let proposal: TxProposalResponse = self.send_idempotent(req_builder).await?;
+
+        let params = ParamsWalletDecode {
+            wallet_id: wallet_id.to_string(),
+            tx_hex: Some(proposal.tx_hex),
+            partial_tx: None,
+        };
+        self.decode(&params).await
+    }
+
+    /// Build a tx proposal sending tokens to a single address
+    pub async fn p2sh_txproposal_build_simple_send_tokens(
+        &self,
+        params: &ParamsP2shTxProposalBuildSimpleSendTokens,
+    ) -> Result<TxProposalResponse, HeadlessCliError> {
+        let mut output = HashMap::<String, HashMapValue>::new();
+        output.insert(String::from("address"), params.address.clone().into());
+        output.insert(String::from("value"), params.value.into());
+        if let Some(token) = &params.token {
+            output.insert(String::from("token"), token.clone().into());
+        }
+        let outputs = vec![HashMapValue::Dict(output)];
+        let mut map: HashMap<&str, HashMapValue> = HashMap::new();
+        map.insert("outputs", outputs.into());
+
+        if let Some(change_address) = &params.change_address {
+            map.insert("change_address", change_address.clone().into());
+        }
+        if let Some(mark_inputs_as_used) = params.mark_inputs_as_used {
+            map.insert("mark_inputs_as_used", mark_inputs_as_used.into());
+        }
+
+        let req_builder = self
+            .request(
+                Method::POST,
+                "/wallet/p2sh/tx-proposal",
+                Some(&params.wallet_id),
+            )?
+            .json(&map);
+        self.send(req_builder).await
+    }
+
+    /// Get this wallet signatures for a tx proposal
+    pub async fn p2sh_txproposal_get_my_signatures(
+        &self,
+        params: &ParamsP2shTxProposalGetMySignatures,
+    ) -> Result<GetMySignaturesResponse, HeadlessCliError> {
+        let mut map: HashMap<&str, HashMapValue> = HashMap::new();
+        map.insert("txHex", params.tx_hex.clone().into());
+
+        let req_builder = self
+            .request(
+                Method::POST,
+                "/wallet/p2sh/tx-proposal/get-my-signatures",
+                Some(&params.wallet_id),
+            )?
+            .json(&map);
+        self.send(req_builder).await
+    }
+
+    /// Get the signatures of several wallets for a tx proposal, concurrently.
+    ///
+    /// The results are in the order of `wallet_ids`, a failure of one wallet
+    /// does not stop the others.
+    pub async fn p2sh_txproposal_collect_signatures(
+        &self,
+        tx_hex: &str,
+        wallet_ids: &[String],
+    ) -> Vec<Result<String, HeadlessCliError>> {
+        let tasks: Vec<_> = wallet_ids
+            .iter()
+            .map(|wallet_id| {
+                let client = self.clone();
+                let params = ParamsP2shTxProposalGetMySignatures {
+                    wallet_id: wallet_id.clone(),
+                    tx_hex: tx_hex.to_string(),
+                };
+                tokio::spawn(async move {
+                    let response = client.p2sh_txproposal_get_my_signatures(&params).await;
+                    response.map(|response| response.signatures)
+                })
+            })
+            .collect();
+
+        let mut results = vec![];
+        for (task, wallet_id) in tasks.into_iter().zip(wallet_ids) {
+            results.push(task.await.unwrap_or_else(|err| {
+                warn!(
+                    "the signature request of {} did not finish: {}",
+                    wallet_id, err
+                );
+                Err(HeadlessCliError::Internal(err))
+            }));
+        }
+        results
+    }
+
+    /// Sign a tx proposal with the collected signatures
+    pub async fn p2sh_txproposal_sign(
+        &self,
+        params: &ParamsP2shTxProposalSign,
+    ) -> Result<TxProposalResponse, HeadlessCliError> {
+        let map = Self::p2sh_txproposal_sign_body(params);
+
+        let req_builder = self
+            .request(
+                Method::POST,
+                "/wallet/p2sh/tx-proposal/sign",
+                Some(&params.wallet_id),
+            )?
+            .json(&map);
+        self.send(req_builder).await
+    }
+
+    /// Sign a tx proposal with the collected signatures and push it to the network
+    pub async fn p2sh_txproposal_sign_and_push(
+        &self,
+        params: &ParamsP2shTxProposalSign,
+    ) -> Result<SendTxResponse, HeadlessCliError> {
+        let map = Self::p2sh_txproposal_sign_body(params);
+
+        let req_builder = self
+            .request(
+                Method::POST,
+                "/wallet/p2sh/tx-proposal/sign-and-push",
+                Some(&params.wallet_id),
+            )?
+            .json(&map);
+        self.send(req_builder).await
+    }
+
+    fn p2sh_txproposal_sign_body(params: &ParamsP2shTxProposalSign) -> HashMap<&str, HashMapValue> {
+        let mut map: HashMap<&str, HashMapValue> = HashMap::new();
+        map.insert("txHex", params.tx_hex.clone().into());
+        map.insert(
+            "signatures",
+            HashMapValue::List(
+                params
+                    .signatures
+                    .iter()
+                    .map(|s| HashMapValue::String(s.clone()))
+                    .collect(),
+            ),
+        );
+        map
+    }
+
+    /// Create a custom token in the given P2SH wallet.
+    pub async fn p2sh_txproposal_create_token(
+        &self,
+        params: &ParamsWalletP2shTxProposalCreateToken,
+    ) -> Result<TxProposalResponse, HeadlessCliError> {
+        let mut map: HashMap<&str, HashMapValue> = HashMap::new();
+        map.insert("name", HashMapValue::String(params.name.clone()));
+        map.insert("symbol", HashMapValue::String(params.symbol.clone()));
+        map.insert("amount", HashMapValue::Int(params.amount));
+
+        if let Some(address) = &params.address {
+            map.insert("address", HashMapValue::String(address.clone()));
+        }
+
+        if let Some(change_address) = &params.change_address {
+            map.insert(
+                "change_address",
+                HashMapValue::String(change_address.clone()),
+            );
+        }
+
+        if let Some(create_mint) = params.create_mint {
+            map.insert("create_mint", HashMapValue::Bool(create_mint));
+        }
+
+        if let Some(mint_authority_address) = &params.mint_authority_address {
+            map.insert(
+                "mint_authority_address",
+                HashMapValue::String(mint_authority_address.clone()),
+            );
+        }
+
+        if let Some(allow_external_mint_authority_address) =
+            params.allow_external_mint_authority_address
+        {
+            map.insert(
+                "allow_external_mint_authority_address",
+                HashMapValue::Bool(allow_external_mint_authority_address),
+            );
+        }
+
+        if let Some(create_melt) = params.create_melt {
+            map.insert("create_melt", HashMapValue::Bool(create_melt));
+        }
+
+        if let Some(melt_authority_address) = &params.melt_authority_address {
+            map.insert(
+                "melt_authority_address",
+                HashMapValue::String(melt_authority_address.clone()),
+            );
+        }
+
+        if let Some(allow_external_melt_authority_address) =
+            params.allow_external_melt_authority_address
+        {
+            map.insert(
+                "allow_external_melt_authority_address",
+                HashMapValue::Bool(allow_external_melt_authority_address),
+            );
+        }
+
+        if let Some(mark_inputs_as_used) = params.mark_inputs_as_used {
+            map.insert(
+                "mark_inputs_as_used",
+                HashMapValue::Bool(mark_inputs_as_used),
+            );
+        }
+
+        let req_builder = self
+            .request(
+                Method::POST,
+                "/wallet/p2sh/tx-proposal/create-token",
+                Some(&params.wallet_id),
+            )?
+            .json(&map);
+        self.send(req_builder).await
+    }
+
+    /// Mint tokens from the given P2SH wallet.
+    pub async fn p2sh_txproposal_mint_tokens(
+        &self,
+        params: &ParamsWalletP2shTxProposalMintTokens,
+    ) -> Result<TxProposalResponse, HeadlessCliError> {
+        let mut map: HashMap<&str, HashMapValue> = HashMap::new();
+        map.insert("token", HashMapValue::String(params.token.clone()));
+        map.insert("amount", HashMapValue::Int(params.amount));
+
+        if let Some(address) = &params.address {
+            map.insert("address", HashMapValue::String(address.clone()));
+        }
+
+        if let Some(change_address) = &params.change_address {
+            map.insert(
+                "change_address",
+                HashMapValue::String(change_address.clone()),
+            );
+        }
+
+        if let Some(create_mint) = params.create_mint {
+            map.insert("create_mint", HashMapValue::Bool(create_mint));
+        }
+
+        if let Some(mint_authority_address) = &params.mint_authority_address {
+            map.insert(
+                "mint_authority_address",
+                HashMapValue::String(mint_authority_address.clone()),
+            );
+        }
+
+        if let Some(allow_external_mint_authority_address) =
+            params.allow_external_mint_authority_address
+        {
+            map.insert(
+                "allow_external_mint_authority_address",
+                HashMapValue::Bool(allow_external_mint_authority_address),
+            );
+        }
+
+        if let Some(mark_inputs_as_used) = params.mark_inputs_as_used {
+            map.insert(
+                "mark_inputs_as_used",
+                HashMapValue::Bool(mark_inputs_as_used),
+            );
+        }
+
+        let req_builder = self
+            .request(
+                Method::POST,
+                "/wallet/p2sh/tx-proposal/mint-tokens",
+                Some(&params.wallet_id),
+            )?
+            .json(&map);
+        self.send(req_builder).await
+    }
+
+    /// Melt tokens from the given P2SH wallet.
+    pub async fn p2sh_txproposal_melt_tokens(
+        &self,
+        params: &ParamsWalletP2shTxProposalMeltTokens,
+    ) -> Result<TxProposalResponse, HeadlessCliError> {
+        let mut map: HashMap<&str, HashMapValue> = HashMap::new();
+        map.insert("token", HashMapValue::String(params.token.clone()));
+        map.insert("amount", HashMapValue::Int(params.amount));
+
+        if let Some(address) = &params.address {
+            map.insert("address", HashMapValue::String(address.clone()));
+        }
+
+        if let Some(deposit_address) = &params.deposit_address {
+            map.insert(
+                "deposit_address",
+                HashMapValue::String(deposit_address.clone()),
+            );
+        }
+
+        if let Some(change_address) = &params.change_address {
+            map.insert(
+                "change_address",
+                HashMapValue::String(change_address.clone()),
+            );
+        }
+
+        if let Some(create_melt) = params.create_melt {
+            map.insert("create_melt", HashMapValue::Bool(create_melt));
+        }
+
+        if let Some(melt_authority_address) = &params.melt_authority_address {
+            map.insert(
+                "melt_authority_address",
+                HashMapValue::String(melt_authority_address.clone()),
+            );
+        }
+
+        if let Some(allow_external_melt_authority_address) =
+            params.allow_external_melt_authority_address
+        {
+            map.insert(
+                "allow_external_melt_authority_address",
+                HashMapValue::Bool(allow_external_melt_authority_address),
+            );
+        }
+
+        if let Some(mark_inputs_as_used) = params.mark_inputs_as_used {
+            map.insert(
+                "mark_inputs_as_used",
+                HashMapValue::Bool(mark_inputs_as_used),
+            );
+        }
+
+        let req_builder = self
+            .request(
+                Method::POST,
+                "/wallet/p2sh/tx-proposal/melt-tokens",
+                Some(&params.wallet_id),
+            )?
+            .json(&map);
+        self.send(req_builder).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::utils::tests::stub_server;
+
+    /// A headless that accepts connections and never answers.
+    async fn silent_headless() -> HeadlessClient {
+        let config = CliConfig {
+            host: stub_server(vec![]).await.host,
+            timeout: Some(Duration::from_millis(200)),
+            retries: 0,
+            ..Default::default()
+        };
+        HeadlessClient::new(config).unwrap()
+    }
+
+    #[tokio::test]
+    async fn mutating_request_timeouts_have_an_unknown_outcome() {
+        let client = silent_headless().await;
+        let params = ParamsWalletSend {
+            wallet_id: "w".to_string(),
+            body: "{}".to_string(),
+        };
+        match client.send_tx(&params).await {
+            Err(HeadlessCliError::Timeout(message)) => {
+                assert!(message.contains("POST"), "{}", message);
+                assert!(message.contains("check the wallet history"), "{}", message);
+            }
+            result => panic!("expected a timeout, got {:?}", result),
+        }
+    }
+
+    #[tokio::test]
+    async fn read_request_timeouts_are_connection_errors() {
+        let client = silent_headless().await;
+        let params = ParamsWalletBalance {
+            wallet_id: "w".to_string(),
+            token: None,
+        };
+        match client.balance(&params).await {
+            Err(HeadlessCliError::Connection(err)) => assert!(err.is_timeout()),
+            result => panic!("expected a connection error, got {:?}", result),
+        }
+    }
+
+    #[tokio::test]
+    async fn dry_run_redacts_every_configured_header() {
+        let config = CliConfig {
+            headers: HashMap::from([("X-Gateway-Key".to_string(), "secret".to_string())]),
+            auth: crate::config::Auth {
+                bearer_token: Some("token".to_string()),
+                ..Default::default()
+            },
+            dry_run: true,
+            ..Default::default()
+        };
+        let client = HeadlessClient::new(config).unwrap();
+        let params = ParamsWalletSend {
+            wallet_id: "w".to_string(),
+            body: "{}".to_string(),
+        };
+        let request = match client.send_tx(&params).await {
+            Err(HeadlessCliError::DryRun(request)) => request,
+            result => panic!("expected a dry run, got {:?}", result),
+        };
+        assert_eq!(request.headers["X-Gateway-Key"], "<redacted>");
+        assert_eq!(request.headers["Authorization"], "<redacted>");
+        assert_eq!(request.headers["x-wallet-id"], "w");
+    }
+}
@@ -0,0 +1,404 @@
+//! Configuration file with named profiles.
+//!
+//! The file is read from `--config`, `HEADLESS_CLI_CONFIG` or
+//! `~/.config/headless_cli/config.toml` and looks like this:
+//!
+//! ```toml
+//! default_profile = "local"
+//!
+//! [profiles.local]
+//! host = "http://localhost:8000"
+//! wallet_id = "default"
+//!
+//! [profiles.staging]
+//! host = "https://headless.staging.example.com"
+//! wallet_id = "payouts"
+//! network = "testnet"
+//! token = "00"
+//! connect_timeout = 5
+//! timeout = 30
+//! retries = 5
+//! retry_backoff_ms = 1000
+//!
+//! [profiles.staging.headers]
+//! X-Team = "payments"
+//!
+//! [profiles.staging.auth]
+//! api_key_header = "X-Api-Key"
+//! api_key = "secret"
+//!
+//! [profiles.internal]
+//! host = "https://headless.internal:8443"
+//! ca_cert = "/etc/headless/ca.pem"
+//! client_cert = "/etc/headless/client.pem"
+//! client_key = "/etc/headless/client.key"
+//! ```
+//!
+//! Values are resolved with the precedence: command line arguments,
+//! environment variables (`HEADLESS_CLI_HOST`, `HEADLESS_CLI_WALLET_ID`,
+//! `HEADLESS_CLI_NETWORK`, `HEADLESS_CLI_TOKEN`, `HEADLESS_CLI_CONNECT_TIMEOUT`, `HEADLESS_CLI_TIMEOUT`,
+//! `HEADLESS_CLI_RETRIES`,
+//! `HEADLESS_CLI_CA_CERT`, `HEADLESS_CLI_CLIENT_CERT`, `HEADLESS_CLI_CLIENT_KEY`),
+//! the selected profile and the defaults from [`CliConfig`].
+//!
+//! Credentials are never read from the command line so they do not end up in
+//! the shell history, only from the profile or the environment
+//! (`HEADLESS_CLI_API_KEY`, `HEADLESS_CLI_API_KEY_HEADER`,
+//! `HEADLESS_CLI_BEARER_TOKEN`, `HEADLESS_CLI_BASIC_USER`, `HEADLESS_CLI_BASIC_PASSWORD`).
+
+use crate::error::HeadlessCliError;
+use crate::params::CliConfig;
+use crate::validation::Network;
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::Deserialize;
+
+/////////////////////////////////////////// Authentication
+
+/// Header used for the api key when `api_key_header` is not set.
+pub const DEFAULT_API_KEY_HEADER: &str = "X-Api-Key";
+
+/// Credentials sent on every request to the headless.
+///
+/// The `Debug` output redacts every secret.
+///
+/// # Examples
+///
+/// ```
+/// use headless_cli::config::Auth;
+///
+/// let auth = Auth {
+///     api_key: Some("secret".to_string()),
+///     basic_user: Some("user".to_string()),
+///     basic_password: Some("pass".to_string()),
+///     ..Default::default()
+/// };
+/// assert_eq!(
+///     auth.headers().unwrap(),
+///     vec![
+///         ("X-Api-Key".to_string(), "secret".to_string()),
+///         ("Authorization".to_string(), "Basic dXNlcjpwYXNz".to_string()),
+///     ]
+/// );
+/// assert!(!format!("{:?}", auth).contains("secret"));
+/// ```
+#[derive(Deserialize, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Auth {
+    /// Header to send the api key on, defaults to [`DEFAULT_API_KEY_HEADER`]
+    pub api_key_header: Option<String>,
+    pub api_key: Option<String>,
+    /// Sent as `Authorization: Bearer <token>`
+    pub bearer_token: Option<String>,
+    /// Sent as `Authorization: Basic <base64(user:password)>`
+    pub basic_user: Option<String>,
+    pub basic_password: Option<String>,
+}
+
+impl Auth {
+    /// Read the credentials from the environment.
+    pub fn from_env() -> Self {
+        Auth {
+            api_key_header: env::var("HEADLESS_CLI_API_KEY_HEADER").ok(),
+            api_key: env::var("HEADLESS_CLI_API_KEY").ok(),
+            bearer_token: env::var("HEADLESS_CLI_BEARER_TOKEN").ok(),
+            basic_user: env::var("HEADLESS_CLI_BASIC_USER").ok(),
+            basic_password: env::var("HEADLESS_CLI_BASIC_PASSWORD").ok(),
+        }
+    }
+
+    /// Merge two credentials, values from `other` take precedence.
+    pub fn merge(self, other: Auth) -> Self {
+        Auth {
+            api_key_header: other.api_key_header.or(self.api_key_header),
+            api_key: other.api_key.or(self.api_key),
+            bearer_token: other.bearer_token.or(self.bearer_token),
+            basic_user: other.basic_user.or(self.basic_user),
+            basic_password: other.basic_password.or(self.basic_password),
+        }
+    }
+
+    /// The headers to send on every request.
+    pub fn headers(&self) -> Result<Vec<(String, String)>, HeadlessCliError> {
+        let mut headers = vec![];
+
+        if let Some(api_key) = &self.api_key {
+            let name = self
+                .api_key_header
+                .as_deref()
+                .unwrap_or(DEFAULT_API_KEY_HEADER);
+            headers.push((name.to_string(), api_key.clone()));
+        }
+
+        let authorization = match (&self.bearer_token, &self.basic_user) {
+            (Some(_), Some(_)) => {
+                return Err(HeadlessCliError::Config(
+                    "bearer token and basic auth cannot be used together".to_string(),
+                ))
+            }
+            (Some(token), None) => Some(format!("Bearer {}", token)),
+            (None, Some(user)) => {
+                let password = self.basic_password.as_deref().unwrap_or("");
+                Some(format!(
+                    "Basic {}",
+                    BASE64.encode(format!("{}:{}", user, password))
+                ))
+            }
+            (None, None) => None,
+        };
+        if let Some(authorization) = authorization {
+            headers.push(("Authorization".to_string(), authorization));
+        }
+
+        Ok(headers)
+    }
+}
+
+impl fmt::Debug for Auth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let redact = |value: &Option<String>| value.as_ref().map(|_| "<redacted>");
+        f.debug_struct("Auth")
+            .field("api_key_header", &self.api_key_header)
+            .field("api_key", &redact(&self.api_key))
+            .field("bearer_token", &redact(&self.bearer_token))
+            .field("basic_user", &self.basic_user)
+            .field("basic_password", &redact(&self.basic_password))
+            .finish()
+    }
+}
+
+/////////////////////////////////////////// Config file
+
+/// A named profile from the config file.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// The base_url to send requests
+    pub host: Option<String>,
+    /// Wallet id used when a command does not specify one
+    pub wallet_id: Option<String>,
+    /// Reject addresses of other networks
+    pub network: Option<Network>,
+    /// Token used when a command does not specify one
+    pub token: Option<String>,
+    /// Connect timeout in seconds
+    pub connect_timeout: Option<u64>,
+    /// Timeout in seconds for the whole request, 0 disables it
+    pub timeout: Option<u64>,
+    /// How many times idempotent requests are retried
+    pub retries: Option<u32>,
+    /// Delay before the first retry in milliseconds, doubled on each retry
+    pub retry_backoff_ms: Option<u64>,
+    /// Also retry requests that change the wallet (e.g. sending a transaction)
+    pub retry_mutating: Option<bool>,
+    /// Extra headers sent on every request
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Credentials sent on every request
+    #[serde(default)]
+    pub auth: Auth,
+    /// PEM file with the CA certificates to trust
+    pub ca_cert: Option<PathBuf>,
+    /// PEM file with the client certificate for mTLS
+    pub client_cert: Option<PathBuf>,
+    /// PEM file with the PKCS#8 key of the client certificate
+    pub client_key: Option<PathBuf>,
+    /// Accept invalid certificates (dangerous)
+    pub insecure: Option<bool>,
+}
+
+impl Profile {
+    /// Read the profile overrides from the environment.
+    pub fn from_env() -> Result<Self, HeadlessCliError> {
+        Ok(Profile {
+            host: env::var("HEADLESS_CLI_HOST").ok(),
+            wallet_id: env::var("HEADLESS_CLI_WALLET_ID").ok(),
+            network: env::var("HEADLESS_CLI_NETWORK")
+                .ok()
+                .map(|network| network.parse().map_err(HeadlessCliError::Config))
+                .transpose()?,
+            token: env::var("HEADLESS_CLI_TOKEN").ok(),
+            connect_timeout: env_number("HEADLESS_CLI_CONNECT_TIMEOUT")?,
+            timeout: env_number("HEADLESS_CLI_TIMEOUT")?,
+            retries: env_number("HEADLESS_CLI_RETRIES")?,
+            retry_backoff_ms: None,
+            retry_mutating: None,
+            headers: HashMap::new(),
+            auth: Auth::from_env(),
+            ca_cert: env::var_os("HEADLESS_CLI_CA_CERT").map(PathBuf::from),
+            client_cert: env::var_os("HEADLESS_CLI_CLIENT_CERT").map(PathBuf::from),
+            client_key: env::var_os("HEADLESS_CLI_CLIENT_KEY").map(PathBuf::from),
+            insecure: None,
+        })
+    }
+
+    /// Merge two profiles, values from `other` take precedence.
+    pub fn merge(mut self, other: Profile) -> Self {
+        self.headers.extend(other.headers);
+        Profile {
+            host: other.host.or(self.host),
+            wallet_id: other.wallet_id.or(self.wallet_id),
+            network: other.network.or(self.network),
+            token: other.token.or(self.token),
+            connect_timeout: other.connect_timeout.or(self.connect_timeout),
+            timeout: other.timeout.or(self.timeout),
+            retries: other.retries.or(self.retries),
+            retry_backoff_ms: other.retry_backoff_ms.or(self.retry_backoff_ms),
+            retry_mutating: other.retry_mutating.or(self.retry_mutating),
+            headers: self.headers,
+            auth: self.auth.merge(other.auth),
+            ca_cert: other.ca_cert.or(self.ca_cert),
+            client_cert: other.client_cert.or(self.client_cert),
+            client_key: other.client_key.or(self.client_key),
+            insecure: other.insecure.or(self.insecure),
+        }
+    }
+
+    /// Override the values of `config` with the ones set on this profile.
+    pub fn apply(self, mut config: CliConfig) -> CliConfig {
+        if let Some(host) = self.host {
+            config.host = host;
+        }
+        if let Some(wallet_id) = self.wallet_id {
+            config.wallet_id = wallet_id;
+        }
+        if let Some(network) = self.network {
+            config.network = Some(network);
+        }
+        if let Some(token) = self.token {
+            config.token = Some(token);
+        }
+        if let Some(connect_timeout) = self.connect_timeout {
+            config.connect_timeout = Duration::from_secs(connect_timeout);
+        }
+        if let Some(timeout) = self.timeout {
+            config.timeout = (timeout > 0).then(|| Duration::from_secs(timeout));
+        }
+        if let Some(retries) = self.retries {
+            config.retries = retries;
+        }
+        if let Some(retry_backoff_ms) = self.retry_backoff_ms {
+            config.retry_backoff = Duration::from_millis(retry_backoff_ms);
+        }
+        if let Some(retry_mutating) = self.retry_mutating {
+            config.retry_mutating = retry_mutating;
+        }
+        config.headers.extend(self.headers);
+        config.auth = config.auth.merge(self.auth);
+        if let Some(ca_cert) = self.ca_cert {
+            config.ca_cert = Some(ca_cert);
+        }
+        if let Some(client_cert) = self.client_cert {
+            config.client_cert = Some(client_cert);
+        }
+        if let Some(client_key) = self.client_key {
+            config.client_key = Some(client_key);
+        }
+        if let Some(insecure) = self.insecure {
+            config.insecure = insecure;
+        }
+        config
+    }
+}
+
+/// The config file, holding the named profiles.
+///
+/// # Examples
+///
+/// ```
+/// use headless_cli::config::ConfigFile;
+///
+/// let config_file = ConfigFile::parse(r#"
+///     default_profile = "local"
+///
+///     [profiles.local]
+///     host = "http://localhost:8000"
+///
+///     [profiles.staging]
+///     host = "https://headless.staging.example.com"
+///     wallet_id = "payouts"
+/// "#).unwrap();
+///
+/// let profile = config_file.profile(None).unwrap();
+/// assert_eq!(profile.host.as_deref(), Some("http://localhost:8000"));
+///
+/// let profile = config_file.profile(Some("staging")).unwrap();
+/// assert_eq!(profile.wallet_id.as_deref(), Some("payouts"));
+///
+/// assert!(config_file.profile(Some("production")).is_err());
+/// ```
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    /// Profile used when none is selected
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+}
+
+impl ConfigFile {
+    /// Parse the contents of a config file.
+    pub fn parse(content: &str) -> Result<Self, HeadlessCliError> {
+        toml::from_str(content).map_err(|err| HeadlessCliError::Config(err.to_string()))
+    }
+
+    /// Load the config file from the given path.
+    pub fn load(path: &Path) -> Result<Self, HeadlessCliError> {
+        let content = fs::read_to_string(path).map_err(|err| {
+            HeadlessCliError::Config(format!("could not read {}: {}", path.display(), err))
+        })?;
+        Self::parse(&content)
+    }
+
+    /// Load the config file from the default path, a missing file is an empty config.
+    pub fn load_default() -> Result<Self, HeadlessCliError> {
+        match default_config_path() {
+            Some(path) if path.exists() => Self::load(&path),
+            _ => Ok(ConfigFile::default()),
+        }
+    }
+
+    /// Get a profile by name, or the default profile if no name is given.
+    /// Without a name or a default profile an empty profile is returned.
+    pub fn profile(&self, name: Option<&str>) -> Result<Profile, HeadlessCliError> {
+        match name.or(self.default_profile.as_deref()) {
+            Some(name) => self
+                .profiles
+                .get(name)
+                .cloned()
+                .ok_or_else(|| HeadlessCliError::Config(format!("profile {} not found", name))),
+            None => Ok(Profile::default()),
+        }
+    }
+}
+
+/// Default path of the config file: `$XDG_CONFIG_HOME/headless_cli/config.toml`,
+/// falling back to `~/.config` (or `%APPDATA%` on windows) if not set.
+pub fn default_config_path() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+
+    Some(config_dir.join("headless_cli").join("config.toml"))
+}
+
+fn env_number<T: FromStr>(name: &str) -> Result<Option<T>, HeadlessCliError> {
+    env::var(name)
+        .ok()
+        .map(|value| {
+            value
+                .parse::<T>()
+                .map_err(|_| HeadlessCliError::Config(format!("{} must be a number", name)))
+        })
+        .transpose()
+}
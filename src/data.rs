@@ -1,13 +1,24 @@
+use std::collections::{BTreeMap, HashMap};
+
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
 /////////////////////////////////////////// Data structures
 
+// The responses keep the fields they do not model in `extra` and skip the
+// absent optional ones, so the json output is what the headless returned.
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DecodedOutput {
     // pub type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub timelock: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub data: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -17,7 +28,10 @@ pub struct HistoryOutput {
     pub script: String,
     pub decoded: DecodedOutput,
     pub token: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub spent_by: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -29,6 +43,8 @@ pub struct HistoryInput {
     pub token: String,
     pub tx_id: String,
     pub index: u32,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -41,26 +57,445 @@ pub struct HistoryTx {
     pub inputs: Vec<HistoryInput>,
     pub outputs: Vec<HistoryOutput>,
     pub parents: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub token_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub token_symbol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub tokens: Option<Vec<String>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
+/// Response of the address-info call.
+///
+/// # Examples
+///
+/// ```
+/// use headless_cli::data::AddressInfoResponse;
+///
+/// let body = r#"{
+///     "success": true,
+///     "total_amount_received": 9007199254740993,
+///     "total_amount_sent": 0,
+///     "total_amount_available": 9007199254740993,
+///     "total_amount_locked": 0,
+///     "token": "00",
+///     "index": 0
+/// }"#;
+/// let response: AddressInfoResponse = serde_json::from_str(body).unwrap();
+/// assert_eq!(response.total_amount_available, Some(9_007_199_254_740_993));
+/// ```
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AddressInfoResponse {
     pub success: bool,
     // For success messages
-    pub total_amount_received: Option<u32>,
-    pub total_amount_sent: Option<u32>,
-    pub total_amount_available: Option<u32>,
-    pub total_amount_locked: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_amount_received: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_amount_sent: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_amount_available: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_amount_locked: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub index: Option<u32>,
     // For fail messages
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AddressesResponse {
     pub addresses: Vec<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// The envelope used by the headless to report a failed call:
+/// `{ "success": false, "error": "..." }`, some endpoints use `message` instead of `error`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ErrorResponse {
+    pub success: bool,
+    pub error: Option<String>,
+    pub message: Option<String>,
+}
+
+/// Response of calls that only report if they succeeded (e.g. start and stop).
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SuccessResponse {
+    pub success: bool,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MultisigPubkeyResponse {
+    pub success: bool,
+    pub xpubkey: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ConfigurationStringResponse {
+    pub success: bool,
+    #[serde(rename = "configurationString")]
+    pub configuration_string: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ServerInfo {
+    pub version: String,
+    pub network: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_weight: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_tx_weight: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_tx_weight_coefficient: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_tx_weight_k: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_deposit_percentage: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reward_spend_min_blocks: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_number_inputs: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_number_outputs: Option<u32>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Status of a started wallet.
+///
+/// # Examples
+///
+/// ```
+/// use headless_cli::data::StatusResponse;
+///
+/// let response: StatusResponse = serde_json::from_str(
+///     r#"{"statusCode": 2, "statusMessage": "Syncing", "network": "testnet", "serverUrl": "http://fullnode", "serverInfo": null}"#,
+/// ).unwrap();
+/// assert!(!response.is_ready());
+/// assert!(!response.is_error());
+/// ```
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusResponse {
+    pub status_code: u32,
+    pub status_message: String,
+    pub network: String,
+    pub server_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_info: Option<ServerInfo>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl StatusResponse {
+    pub const CLOSED: u32 = 0;
+    pub const CONNECTING: u32 = 1;
+    pub const SYNCING: u32 = 2;
+    pub const READY: u32 = 3;
+    pub const ERROR: u32 = 4;
+    pub const PROCESSING: u32 = 5;
+
+    /// The wallet is synced and accepts calls.
+    pub fn is_ready(&self) -> bool {
+        self.status_code == Self::READY
+    }
+
+    /// The wallet failed to start or sync and will not become ready.
+    pub fn is_error(&self) -> bool {
+        self.status_code == Self::ERROR
+    }
+}
+
+/// Balance of a token on the wallet.
+///
+/// # Examples
+///
+/// ```
+/// use headless_cli::data::BalanceResponse;
+///
+/// let response: BalanceResponse =
+///     serde_json::from_str(r#"{"available": 5000000000, "locked": 0}"#).unwrap();
+/// assert_eq!(response.available, 5_000_000_000);
+/// assert_eq!(
+///     serde_json::to_string(&response).unwrap(),
+///     r#"{"available":5000000000,"locked":0}"#
+/// );
+/// ```
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BalanceResponse {
+    pub available: u64,
+    pub locked: u64,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AddressResponse {
+    pub address: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AddressIndexResponse {
+    pub success: bool,
+    pub index: u32,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// An input of a transaction decoded by the headless.
+/// Only `tx_id` and `index` are guaranteed, the spent output fields are
+/// filled when the headless knows the spent transaction.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DecodedTxInput {
+    #[serde(rename = "txId", alias = "tx_id")]
+    pub tx_id: String,
+    pub index: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decoded: Option<DecodedOutput>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<u64>,
+    #[serde(rename = "tokenData", alias = "token_data")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_data: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub script: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signed: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mine: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// An output of a transaction decoded by the headless.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DecodedTxOutput {
+    pub value: u64,
+    #[serde(rename = "tokenData", alias = "token_data")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_data: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub script: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decoded: Option<DecodedOutput>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mine: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DecodedTx {
+    #[serde(default)]
+    pub tokens: Vec<String>,
+    #[serde(default)]
+    pub inputs: Vec<DecodedTxInput>,
+    #[serde(default)]
+    pub outputs: Vec<DecodedTxOutput>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl DecodedTx {
+    /// Uid of the token of an output, `None` if the headless did not tell.
+    ///
+    /// `token_data` is 0 for HTR or the 1-based index of the token on `tokens`.
+    pub fn output_token<'a>(&'a self, output: &'a DecodedTxOutput) -> Option<&'a str> {
+        match output.token_data.map(|token_data| token_data & 0x7f) {
+            Some(index) if index > 0 => self.tokens.get(index as usize - 1).map(String::as_str),
+            _ => output.token.as_deref(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DecodeResponse {
+    pub success: bool,
+    pub tx: DecodedTx,
+    /// Balance of the wallet on this transaction, keyed by token uid.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balance: Option<HashMap<String, serde_json::Value>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TxConfirmationResponse {
+    pub success: bool,
+    #[serde(rename = "confirmationNumber")]
+    pub confirmation_number: u64,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SentTxInput {
+    pub hash: String,
+    pub index: u32,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SentTxOutput {
+    pub value: u64,
+    #[serde(rename = "tokenData", alias = "token_data")]
+    pub token_data: u8,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Response of the calls that push a transaction to the network:
+/// simple-send-tx, send-tx, create-token, mint-tokens, melt-tokens, create-nft
+/// and the p2sh sign-and-push.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SendTxResponse {
+    pub success: bool,
+    pub hash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weight: Option<f64>,
+    #[serde(default)]
+    pub parents: Vec<String>,
+    #[serde(default)]
+    pub inputs: Vec<SentTxInput>,
+    #[serde(default)]
+    pub outputs: Vec<SentTxOutput>,
+    #[serde(default)]
+    pub tokens: Vec<String>,
+    // Only for token creation transactions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    #[serde(rename = "configurationString")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub configuration_string: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Utxo {
+    pub address: String,
+    pub amount: u64,
+    pub tx_id: String,
+    pub locked: bool,
+    pub index: u32,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UtxoFilterResponse {
+    pub total_amount_available: u64,
+    pub total_utxos_available: u64,
+    pub total_amount_locked: u64,
+    pub total_utxos_locked: u64,
+    pub utxos: Vec<Utxo>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UtxoConsolidationResponse {
+    pub success: bool,
+    #[serde(rename = "txId")]
+    pub tx_id: String,
+    pub utxos: Vec<Utxo>,
+    pub total_amount: u64,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Response of the p2sh calls that build or sign a tx proposal.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TxProposalResponse {
+    pub success: bool,
+    #[serde(rename = "txHex")]
+    pub tx_hex: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetMySignaturesResponse {
+    pub success: bool,
+    /// Serialized signatures of this wallet, to be sent to the wallet signing the proposal.
+    pub signatures: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// A request that was not sent because of `--dry-run`.
+///
+/// Credentials are redacted from the headers.
+#[derive(Serialize, Debug)]
+pub struct DryRunRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: BTreeMap<String, String>,
+    pub body: Option<serde_json::Value>,
+    /// Transaction the request would send, decoded by the headless when it can be built
+    /// without pushing it
+    pub preview: Option<serde_json::Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_keeps_unmodeled_fields() {
+        let json = serde_json::json!({
+            "statusCode": 3,
+            "statusMessage": "Ready",
+            "network": "testnet",
+            "serverUrl": "http://fullnode",
+            "serverInfo": {
+                "version": "0.59.0",
+                "network": "testnet-golf",
+                "min_weight": 14.0,
+                "decimal_places": 2,
+                "native_token": {"name": "Hathor", "symbol": "HTR"}
+            }
+        });
+        let response: StatusResponse = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&response).unwrap(), json);
+    }
+
+    #[test]
+    fn absent_optional_fields_are_not_serialized() {
+        let json = serde_json::json!({"value": 100, "tokenData": 0, "script": "dqkU"});
+        let output: DecodedTxOutput = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&output).unwrap(), json);
+
+        let json = serde_json::json!({"txId": "00aa", "index": 0, "tokenData": 1});
+        let input: DecodedTxInput = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&input).unwrap(), json);
+    }
 }
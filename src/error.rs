@@ -0,0 +1,155 @@
+use crate::data::DryRunRequest;
+
+use std::fmt;
+
+/////////////////////////////////////////// Errors
+
+/// Errors from calling the headless or from the cli itself.
+///
+/// Each variant maps to a distinct process exit code (see [`HeadlessCliError::exit_code`]):
+///
+/// | code | error                                              |
+/// |------|----------------------------------------------------|
+/// | 2    | `InvalidInput`: the arguments given are not valid  |
+/// | 3    | `Connection`: the headless could not be reached    |
+/// | 4    | `HttpStatus`: the headless answered a non-2xx code |
+/// | 5    | `Headless`: the headless answered `success: false` |
+/// | 6    | `Decode`: the response could not be parsed         |
+/// | 7    | `InvalidUrl`: the host or path are not a valid url |
+/// | 8    | `Config`: the config file or environment is invalid |
+/// | 9    | `Timeout`: gave up waiting for the wallet, a transaction or a response |
+/// | 10   | `Voided`: the transaction was voided while waiting for it |
+/// | 11   | `Aborted`: the user did not confirm the command    |
+/// | 12   | `InsufficientFunds`: the wallet cannot pay the outputs |
+/// | 13   | `PolicyViolation`: a tx proposal does not match the signer policy |
+/// | 14   | `PartialFailure`: some rows of a batch were rejected |
+/// | 15   | `Internal`: a task of the cli panicked or was cancelled |
+///
+/// `DryRun` is not a failure, the binary prints the request and exits with 0.
+#[derive(Debug)]
+pub enum HeadlessCliError {
+    /// The request could not be sent or the response could not be read.
+    Connection(reqwest::Error),
+    /// The headless answered with a non-2xx status code.
+    HttpStatus { status: u16, body: String },
+    /// The headless answered with `{ "success": false, "error": "..." }`.
+    Headless { message: String, body: String },
+    /// The response is not valid json or does not have the expected fields.
+    Decode(serde_json::Error),
+    /// The host or path do not form a valid url.
+    InvalidUrl(url::ParseError),
+    /// The arguments given are not valid.
+    InvalidInput(String),
+    /// The config file or environment variables are not valid.
+    Config(String),
+    /// Gave up waiting for a condition (e.g. the wallet to be ready) or for the
+    /// response to a request that changes the wallet, which may have been executed.
+    Timeout(String),
+    /// The transaction being waited for was voided.
+    Voided(String),
+    /// The user did not confirm a command that moves funds.
+    Aborted,
+    /// The wallet balance does not cover the outputs, one message per token.
+    InsufficientFunds(String),
+    /// The outputs of a tx proposal are not the ones expected by the signer policy.
+    PolicyViolation(String),
+    /// Some rows of a batch were rejected by the headless, the others were sent.
+    PartialFailure(String),
+    /// A task of the cli panicked or was cancelled, this is a bug of the cli.
+    Internal(tokio::task::JoinError),
+    /// The request changes the wallet and `dry_run` is set, so it was not sent.
+    DryRun(Box<DryRunRequest>),
+}
+
+impl HeadlessCliError {
+    /// The process exit code for this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            HeadlessCliError::InvalidInput(_) => 2,
+            HeadlessCliError::Connection(_) => 3,
+            HeadlessCliError::HttpStatus { .. } => 4,
+            HeadlessCliError::Headless { .. } => 5,
+            HeadlessCliError::Decode(_) => 6,
+            HeadlessCliError::InvalidUrl(_) => 7,
+            HeadlessCliError::Config(_) => 8,
+            HeadlessCliError::Timeout(_) => 9,
+            HeadlessCliError::Voided(_) => 10,
+            HeadlessCliError::Aborted => 11,
+            HeadlessCliError::InsufficientFunds(_) => 12,
+            HeadlessCliError::PolicyViolation(_) => 13,
+            HeadlessCliError::PartialFailure(_) => 14,
+            HeadlessCliError::Internal(_) => 15,
+            HeadlessCliError::DryRun(_) => 0,
+        }
+    }
+
+    /// The raw body of the headless response, if the error came from a response.
+    pub fn response_body(&self) -> Option<&str> {
+        match self {
+            HeadlessCliError::HttpStatus { body, .. } | HeadlessCliError::Headless { body, .. } => {
+                Some(body)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for HeadlessCliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeadlessCliError::Connection(err) => write!(f, "Connection error: {}", err),
+            HeadlessCliError::HttpStatus { status, body } => {
+                write!(f, "Headless answered with status {}: {}", status, body)
+            }
+            HeadlessCliError::Headless { message, .. } => write!(f, "Headless error: {}", message),
+            HeadlessCliError::Decode(err) => write!(f, "Invalid response: {}", err),
+            HeadlessCliError::InvalidUrl(err) => write!(f, "Invalid url: {}", err),
+            HeadlessCliError::InvalidInput(message) => write!(f, "Invalid input: {}", message),
+            HeadlessCliError::Config(message) => write!(f, "Invalid config: {}", message),
+            HeadlessCliError::Timeout(message) => write!(f, "Timeout: {}", message),
+            HeadlessCliError::Voided(tx_id) => write!(f, "Transaction {} was voided", tx_id),
+            HeadlessCliError::Aborted => write!(f, "Aborted, nothing was sent"),
+            HeadlessCliError::InsufficientFunds(message) => {
+                write!(f, "Insufficient funds: {}", message)
+            }
+            HeadlessCliError::PolicyViolation(message) => {
+                write!(f, "Policy violation: {}", message)
+            }
+            HeadlessCliError::PartialFailure(message) => write!(f, "Partial failure: {}", message),
+            HeadlessCliError::Internal(err) => write!(f, "Internal error: {}", err),
+            HeadlessCliError::DryRun(request) => {
+                write!(f, "Dry run, not sent: {} {}", request.method, request.url)
+            }
+        }
+    }
+}
+
+impl std::error::Error for HeadlessCliError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HeadlessCliError::Connection(err) => Some(err),
+            HeadlessCliError::Decode(err) => Some(err),
+            HeadlessCliError::InvalidUrl(err) => Some(err),
+            HeadlessCliError::Internal(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for HeadlessCliError {
+    fn from(err: reqwest::Error) -> Self {
+        HeadlessCliError::Connection(err)
+    }
+}
+
+impl From<serde_json::Error> for HeadlessCliError {
+    fn from(err: serde_json::Error) -> Self {
+        HeadlessCliError::Decode(err)
+    }
+}
+
+impl From<url::ParseError> for HeadlessCliError {
+    fn from(err: url::ParseError) -> Self {
+        HeadlessCliError::InvalidUrl(err)
+    }
+}
@@ -1,69 +1,212 @@
-use crate::data::*;
-use crate::methods::*;
-use crate::params::*;
-use crate::utils::*;
+use headless_cli::amount::Amount;
+use headless_cli::batch;
+use headless_cli::client::HeadlessClient;
+use headless_cli::data::{DecodedTx, DryRunRequest, SendTxResponse, StatusResponse};
+use headless_cli::error::HeadlessCliError;
+use headless_cli::output::{format_output, format_query, Tabular};
+use headless_cli::params::*;
+use headless_cli::proposal::{OutputPolicy, Proposal};
+use headless_cli::send::{build_send_tx_body, SendOutput};
+use headless_cli::tx::{verify_hash, Transaction};
+use headless_cli::utils::read_input_file;
+use headless_cli::validation::validate_address_network;
+
+use std::collections::HashMap;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
 
-use std::collections::{HashMap, HashSet};
+/////////////////////////////////////////// handlers
 
-use log::debug;
-use serde_json::json;
+/// Print the parsed response (or the queried field) in the configured output format
+fn print_response<T: Tabular>(
+    client: &HeadlessClient,
+    response: &T,
+) -> Result<(), HeadlessCliError> {
+    let config = client.config();
+    let output = match &config.query {
+        Some(path) => format_query(response, path, config.output)?,
+        None => format_output(response, config.output)?,
+    };
+    println!("{}", output);
+    Ok(())
+}
 
-/////////////////////////////////////////// handlers
+/// Print the request that was not sent because of `--dry-run`
+pub fn handle_dry_run(
+    client: &HeadlessClient,
+    request: &DryRunRequest,
+) -> Result<(), HeadlessCliError> {
+    print_response(client, request)
+}
 
-/// Start a wallet
+/// Whether the user has to confirm a command that moves funds.
 ///
-/// # Arguments
-///
-/// * `params` - arguments to configure the call being made
-///
-pub async fn handle_start(params: ParamsStart) -> Result<(), Box<dyn std::error::Error>> {
-    let mut map: HashMap<&str, HashMapValue> = HashMap::new();
-    map.insert("seedKey", params.seed_key.into());
-    map.insert("wallet-id", params.wallet_id.into());
-
-    if let Some(passphrase) = params.passphrase {
-        map.insert("passphrase", passphrase.into());
+/// Not needed with `--yes` or on a dry run, a stdin that is not a terminal requires `--yes`.
+fn confirmation_required(client: &HeadlessClient) -> Result<bool, HeadlessCliError> {
+    let config = client.config();
+    if config.yes || config.dry_run {
+        return Ok(false);
     }
-
-    if let Some(scan_policy) = params.scan_policy {
-        map.insert("scanPolicy", scan_policy.into());
+    if !io::stdin().is_terminal() {
+        return Err(HeadlessCliError::InvalidInput(
+            "stdin is not a terminal, use --yes to move funds without confirmation".to_string(),
+        ));
     }
+    Ok(true)
+}
 
-    if let Some(gap_limit) = params.gap_limit {
-        map.insert("gapLimit", gap_limit.into());
+/// Show what the command will do on stderr and ask the user to confirm it
+fn confirm(action: &str, lines: &[String]) -> Result<(), HeadlessCliError> {
+    eprintln!("About to {}:", action);
+    for line in lines.iter() {
+        eprintln!("  {}", line);
     }
+    eprint!("Continue? [y/N] ");
+    io::stderr().flush().ok();
 
-    if let Some(policy_start_index) = params.policy_start_index {
-        map.insert("policyStartIndex", policy_start_index.into());
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .map_err(|err| HeadlessCliError::InvalidInput(err.to_string()))?;
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => Ok(()),
+        _ => Err(HeadlessCliError::Aborted),
     }
+}
 
-    if let Some(policy_end_index) = params.policy_end_index {
-        map.insert("policyEndIndex", policy_end_index.into());
+/// With `--check-balance`, fail early if the wallet cannot pay the outputs of a `send-tx` body
+async fn check_funds(
+    client: &HeadlessClient,
+    wallet_id: &str,
+    body: &str,
+) -> Result<(), HeadlessCliError> {
+    if !client.config().check_balance {
+        return Ok(());
     }
+    client.check_funds(wallet_id, body).await
+}
 
-    if let Some(history_sync_mode) = params.history_sync_mode {
-        map.insert("history_sync_mode", history_sync_mode.into());
-    }
+/// Symbol of the token for the confirmation, the uid if the headless does not know it
+async fn token_symbol(client: &HeadlessClient, token: Option<&str>) -> String {
+    let token = match token {
+        None | Some("00") => return "HTR".to_string(),
+        Some(token) => token,
+    };
+    client
+        .token_symbol(token)
+        .await
+        .unwrap_or_else(|_| token.to_string())
+}
 
-    if params.multisig {
-        map.insert("multisig", true.into());
+/// `amount SYMBOL to address` line of a confirmation
+async fn output_line(
+    client: &HeadlessClient,
+    value: u64,
+    token: Option<&str>,
+    address: Option<&str>,
+) -> String {
+    format!(
+        "{} {} to {}",
+        Amount::from_units(value),
+        token_symbol(client, token).await,
+        address.unwrap_or("unknown address")
+    )
+}
 
-        if let Some(multisig_key) = params.multisig_key {
-            map.insert("multisigKey", multisig_key.into());
-        }
-    }
+fn change_line(change_address: Option<&str>) -> String {
+    format!(
+        "change to {}",
+        change_address.unwrap_or("an address of the wallet")
+    )
+}
 
-    let url = build_headless_url(&params.config.host, "/start")?;
+/// Wait for the wallet to be ready, showing the progress on stderr
+///
+/// # Arguments
+///
+/// * `client` - client used to call the headless
+/// * `wallet_id` - which wallet to wait for
+/// * `timeout` - how long to wait
+///
+async fn wait_ready(
+    client: &HeadlessClient,
+    wallet_id: &str,
+    timeout: Duration,
+) -> Result<StatusResponse, HeadlessCliError> {
+    let started = Instant::now();
+    let mut last_message = String::new();
+
+    client
+        .wait_ready(wallet_id, timeout, |status| {
+            if status.status_message != last_message {
+                eprintln!(
+                    "wallet {}: {} ({}s)",
+                    wallet_id,
+                    status.status_message,
+                    started.elapsed().as_secs()
+                );
+                last_message = status.status_message.clone();
+            }
+        })
+        .await
+}
 
-    let text_response = build_client(&params.config)?
-        .post(url)
-        .json(&map)
-        .send()
-        .await?
-        .text()
+/// Wait for the transaction confirmations, showing the progress on stderr
+///
+/// # Arguments
+///
+/// * `client` - client used to call the headless
+/// * `wallet_id` - wallet that sent the transaction
+/// * `tx_id` - transaction to wait for
+/// * `wait` - how many confirmations and for how long, nothing is done if not set
+///
+async fn wait_confirmations(
+    client: &HeadlessClient,
+    wallet_id: &str,
+    tx_id: &str,
+    wait: Option<ParamsWaitConfirmations>,
+) -> Result<(), HeadlessCliError> {
+    let params = match wait {
+        Some(params) => params,
+        None => return Ok(()),
+    };
+    let mut last_confirmations = None;
+
+    client
+        .wait_confirmations(wallet_id, tx_id, &params, |confirmations| {
+            if last_confirmations != Some(confirmations) {
+                eprintln!(
+                    "tx {}: {}/{} confirmations",
+                    tx_id, confirmations, params.confirmations
+                );
+                last_confirmations = Some(confirmations);
+            }
+        })
         .await?;
+    Ok(())
+}
 
-    println!("{}", text_response);
+/// Start a wallet
+///
+/// # Arguments
+///
+/// * `client` - client used to call the headless
+/// * `params` - arguments to configure the call being made
+/// * `wait` - wait this long for the wallet to be ready
+///
+pub async fn handle_start(
+    client: &HeadlessClient,
+    params: ParamsStart,
+    wait: Option<Duration>,
+) -> Result<(), HeadlessCliError> {
+    let response = client.start(&params).await?;
+
+    if let Some(timeout) = wait {
+        wait_ready(client, &params.wallet_id, timeout).await?;
+    }
+
+    print_response(client, &response)?;
     Ok(())
 }
 
@@ -71,24 +214,22 @@ pub async fn handle_start(params: ParamsStart) -> Result<(), Box<dyn std::error:
 ///
 /// # Arguments
 ///
+/// * `client` - client used to call the headless
 /// * `params` - arguments to configure the call being made
+/// * `wait` - wait this long for the wallet to be ready
 ///
-pub async fn handle_hsm_start(params: ParamsHsmStart) -> Result<(), Box<dyn std::error::Error>> {
-    let mut map = HashMap::new();
-    map.insert("hsm-key", params.hsm_key);
-    map.insert("wallet-id", params.wallet_id);
-
-    let url = build_headless_url(&params.config.host, "/hsm/start")?;
+pub async fn handle_hsm_start(
+    client: &HeadlessClient,
+    params: ParamsHsmStart,
+    wait: Option<Duration>,
+) -> Result<(), HeadlessCliError> {
+    let response = client.hsm_start(&params).await?;
 
-    let text_response = build_client(&params.config)?
-        .post(url)
-        .json(&map)
-        .send()
-        .await?
-        .text()
-        .await?;
+    if let Some(timeout) = wait {
+        wait_ready(client, &params.wallet_id, timeout).await?;
+    }
 
-    println!("{}", text_response);
+    print_response(client, &response)?;
     Ok(())
 }
 
@@ -96,26 +237,22 @@ pub async fn handle_hsm_start(params: ParamsHsmStart) -> Result<(), Box<dyn std:
 ///
 /// # Arguments
 ///
+/// * `client` - client used to call the headless
 /// * `params` - arguments to configure the call being made
+/// * `wait` - wait this long for the wallet to be ready
 ///
 pub async fn handle_fireblocks_start(
+    client: &HeadlessClient,
     params: ParamsFireblocksStart,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut map = HashMap::new();
-    map.insert("xpub", params.xpub);
-    map.insert("wallet-id", params.wallet_id);
-
-    let url = build_headless_url(&params.config.host, "/fireblocks/start")?;
-
-    let text_response = build_client(&params.config)?
-        .post(url)
-        .json(&map)
-        .send()
-        .await?
-        .text()
-        .await?;
+    wait: Option<Duration>,
+) -> Result<(), HeadlessCliError> {
+    let response = client.fireblocks_start(&params).await?;
 
-    println!("{}", text_response);
+    if let Some(timeout) = wait {
+        wait_ready(client, &params.wallet_id, timeout).await?;
+    }
+
+    print_response(client, &response)?;
     Ok(())
 }
 
@@ -123,22 +260,41 @@ pub async fn handle_fireblocks_start(
 ///
 /// # Arguments
 ///
+/// * `client` - client used to call the headless
 /// * `params` - arguments to configure the call being made
 ///
 pub async fn handle_configuration_string(
+    client: &HeadlessClient,
     params: ParamsConfigString,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, "/configuration-string")?;
-
-    let text_response = build_client(&params.config)?
-        .get(url)
-        .query(&[("token", params.token)])
-        .send()
-        .await?
-        .text()
-        .await?;
+) -> Result<(), HeadlessCliError> {
+    let response = client.configuration_string(&params).await?;
 
-    println!("{}", text_response);
+    print_response(client, &response)?;
+    Ok(())
+}
+
+/// Decode an address offline, no request is made.
+pub fn handle_validate_address(
+    client: &HeadlessClient,
+    address: &str,
+) -> Result<(), HeadlessCliError> {
+    let address = validate_address_network(address, client.config().network)?;
+
+    print_response(client, &address)?;
+    Ok(())
+}
+
+/// Compute the hashes of a transaction locally, no request is made.
+///
+/// Fails when the hash is not `expect`.
+pub fn handle_tx_hash(
+    client: &HeadlessClient,
+    tx_hex: &str,
+    expect: Option<&str>,
+) -> Result<(), HeadlessCliError> {
+    let hashes = verify_hash(tx_hex, expect)?;
+
+    print_response(client, &hashes)?;
     Ok(())
 }
 
@@ -146,29 +302,16 @@ pub async fn handle_configuration_string(
 ///
 /// # Arguments
 ///
+/// * `client` - client used to call the headless
 /// * `params` - arguments to configure the call being made
 ///
 pub async fn handle_multisig_pubkey(
+    client: &HeadlessClient,
     params: ParamsMultisigPubkey,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut map = HashMap::new();
-    map.insert("seedKey", params.seed_key);
-
-    if let Some(passphrase) = params.passphrase {
-        map.insert("passphrase", passphrase);
-    }
-
-    let url = build_headless_url(&params.config.host, "/multisig-pubkey")?;
-
-    let text_response = build_client(&params.config)?
-        .post(url)
-        .json(&map)
-        .send()
-        .await?
-        .text()
-        .await?;
+) -> Result<(), HeadlessCliError> {
+    let response = client.multisig_pubkey(&params).await?;
 
-    println!("{}", text_response);
+    print_response(client, &response)?;
     Ok(())
 }
 
@@ -176,47 +319,52 @@ pub async fn handle_multisig_pubkey(
 ///
 /// # Arguments
 ///
-/// * `params` - Base configuration all cli calls share
+/// * `client` - client used to call the headless
 /// * `wallet_id` - which wallet to fetch the status
 ///
 pub async fn handle_status(
-    params: CliConfig,
+    client: &HeadlessClient,
     wallet_id: String,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.host, "/wallet/status")?;
-
-    let text_response = build_client(&params)?
-        .get(url)
-        .header("X-Wallet-Id", wallet_id)
-        .send()
-        .await?
-        .text()
-        .await?;
+) -> Result<(), HeadlessCliError> {
+    let response = client.status(&wallet_id).await?;
 
-    println!("{}", text_response);
+    print_response(client, &response)?;
     Ok(())
 }
 
-/// Get balance of a token in the given wallet
+/// Wait until the wallet is ready and print its status
 ///
 /// # Arguments
 ///
-/// * `params` - arguments to configure the call being made
+/// * `client` - client used to call the headless
+/// * `wallet_id` - which wallet to wait for
+/// * `timeout` - how long to wait
 ///
-pub async fn handle_balance(params: ParamsWalletBalance) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, "/wallet/balance")?;
-
-    let mut req_builder = build_client(&params.config)?
-        .get(url)
-        .header("X-Wallet-Id", params.wallet_id);
+pub async fn handle_wait_ready(
+    client: &HeadlessClient,
+    wallet_id: String,
+    timeout: Duration,
+) -> Result<(), HeadlessCliError> {
+    let response = wait_ready(client, &wallet_id, timeout).await?;
 
-    if let Some(token) = params.token {
-        req_builder = req_builder.query(&[("token", token)]);
-    }
+    print_response(client, &response)?;
+    Ok(())
+}
 
-    let text_response = req_builder.send().await?.text().await?;
+/// Get balance of a token in the given wallet
+///
+/// # Arguments
+///
+/// * `client` - client used to call the headless
+/// * `params` - arguments to configure the call being made
+///
+pub async fn handle_balance(
+    client: &HeadlessClient,
+    params: ParamsWalletBalance,
+) -> Result<(), HeadlessCliError> {
+    let response = client.balance(&params).await?;
 
-    println!("{}", text_response);
+    print_response(client, &response)?;
     Ok(())
 }
 
@@ -224,26 +372,16 @@ pub async fn handle_balance(params: ParamsWalletBalance) -> Result<(), Box<dyn s
 ///
 /// # Arguments
 ///
+/// * `client` - client used to call the headless
 /// * `params` - arguments to configure the call being made
 ///
-pub async fn handle_address(params: ParamsWalletAddress) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, "/wallet/address")?;
-
-    let mut req_builder = build_client(&params.config)?
-        .get(url)
-        .header("X-Wallet-Id", params.wallet_id);
-
-    if let Some(index) = params.index {
-        req_builder = req_builder.query(&[("index", index)]);
-    }
-
-    if let Some(mark_as_used) = params.mark_as_used {
-        req_builder = req_builder.query(&[("mark_as_used", mark_as_used)]);
-    }
+pub async fn handle_address(
+    client: &HeadlessClient,
+    params: ParamsWalletAddress,
+) -> Result<(), HeadlessCliError> {
+    let response = client.address(&params).await?;
 
-    let text_response = req_builder.send().await?.text().await?;
-
-    println!("{}", text_response);
+    print_response(client, &response)?;
     Ok(())
 }
 
@@ -251,25 +389,16 @@ pub async fn handle_address(params: ParamsWalletAddress) -> Result<(), Box<dyn s
 ///
 /// # Arguments
 ///
+/// * `client` - client used to call the headless
 /// * `params` - arguments to configure the call being made
 ///
 pub async fn handle_address_info(
+    client: &HeadlessClient,
     params: ParamsWalletAddressInfo,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, "/wallet/address-info")?;
-
-    let mut req_builder = build_client(&params.config)?
-        .get(url)
-        .header("X-Wallet-Id", params.wallet_id)
-        .query(&[("address", params.address)]);
-
-    if let Some(token) = params.token {
-        req_builder = req_builder.query(&[("token", token)]);
-    }
-
-    let text_response = req_builder.send().await?.text().await?;
+) -> Result<(), HeadlessCliError> {
+    let response = client.address_info(&params).await?;
 
-    println!("{}", text_response);
+    print_response(client, &response)?;
     Ok(())
 }
 
@@ -277,23 +406,16 @@ pub async fn handle_address_info(
 ///
 /// # Arguments
 ///
+/// * `client` - client used to call the headless
 /// * `params` - arguments to configure the call being made
 ///
 pub async fn handle_address_index(
+    client: &HeadlessClient,
     params: ParamsWalletAddressIndex,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, "/wallet/address-index")?;
-
-    let text_response = build_client(&params.config)?
-        .get(url)
-        .header("X-Wallet-Id", params.wallet_id)
-        .query(&[("address", params.address)])
-        .send()
-        .await?
-        .text()
-        .await?;
+) -> Result<(), HeadlessCliError> {
+    let response = client.address_index(&params).await?;
 
-    println!("{}", text_response);
+    print_response(client, &response)?;
     Ok(())
 }
 
@@ -301,22 +423,16 @@ pub async fn handle_address_index(
 ///
 /// # Arguments
 ///
+/// * `client` - client used to call the headless
 /// * `params` - arguments to configure the call being made
 ///
 pub async fn handle_addresses(
+    client: &HeadlessClient,
     params: ParamsWalletAddresses,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, "/wallet/addresses")?;
-
-    let text_response = build_client(&params.config)?
-        .get(url)
-        .header("X-Wallet-Id", params.wallet_id)
-        .send()
-        .await?
-        .text()
-        .await?;
+) -> Result<(), HeadlessCliError> {
+    let response = client.addresses(&params).await?;
 
-    println!("{}", text_response);
+    print_response(client, &response)?;
     Ok(())
 }
 
@@ -324,24 +440,16 @@ pub async fn handle_addresses(
 ///
 /// # Arguments
 ///
+/// * `client` - client used to call the headless
 /// * `params` - arguments to configure the call being made
 ///
 pub async fn handle_tx_history(
+    client: &HeadlessClient,
     params: ParamsWalletTxHistory,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, "/wallet/tx-history")?;
-
-    let mut req_builder = build_client(&params.config)?
-        .get(url)
-        .header("X-Wallet-Id", params.wallet_id);
-
-    if let Some(limit) = params.limit {
-        req_builder = req_builder.query(&[("limit", limit)]);
-    }
-
-    let text_response = req_builder.send().await?.text().await?;
+) -> Result<(), HeadlessCliError> {
+    let response = client.tx_history(&params).await?;
 
-    println!("{}", text_response);
+    print_response(client, &response)?;
     Ok(())
 }
 
@@ -349,23 +457,16 @@ pub async fn handle_tx_history(
 ///
 /// # Arguments
 ///
+/// * `client` - client used to call the headless
 /// * `params` - arguments to configure the call being made
 ///
 pub async fn handle_transaction(
+    client: &HeadlessClient,
     params: ParamsWalletTransaction,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, "/wallet/transaction")?;
-
-    let text_response = build_client(&params.config)?
-        .get(url)
-        .header("X-Wallet-Id", params.wallet_id)
-        .query(&[("id", params.id)])
-        .send()
-        .await?
-        .text()
-        .await?;
+) -> Result<(), HeadlessCliError> {
+    let response = client.transaction(&params).await?;
 
-    println!("{}", text_response);
+    print_response(client, &response)?;
     Ok(())
 }
 
@@ -373,31 +474,32 @@ pub async fn handle_transaction(
 ///
 /// # Arguments
 ///
+/// * `client` - client used to call the headless
 /// * `params` - arguments to configure the call being made
 ///
-pub async fn handle_decode(params: ParamsWalletDecode) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, "/wallet/decode")?;
+pub async fn handle_decode(
+    client: &HeadlessClient,
+    params: ParamsWalletDecode,
+) -> Result<(), HeadlessCliError> {
+    let response = client.decode(&params).await?;
 
-    let mut map = HashMap::new();
-
-    if let Some(tx_hex) = params.tx_hex {
-        map.insert("txHex", tx_hex);
-    }
-
-    if let Some(partial_tx) = params.partial_tx {
-        map.insert("partial_tx", partial_tx);
-    }
-
-    let text_response = build_client(&params.config)?
-        .post(url)
-        .header("X-Wallet-Id", params.wallet_id)
-        .json(&map)
-        .send()
-        .await?
-        .text()
-        .await?;
+    print_response(client, &response)?;
+    Ok(())
+}
 
-    println!("{}", text_response);
+/// Decode a transaction locally, no request is made.
+pub fn handle_decode_offline(
+    client: &HeadlessClient,
+    tx_hex: &str,
+) -> Result<(), HeadlessCliError> {
+    let network = client.config().network.ok_or_else(|| {
+        HeadlessCliError::InvalidInput(
+            "--network is required to decode the addresses offline".to_string(),
+        )
+    })?;
+    let decoded = Transaction::from_hex(tx_hex)?.decode(network);
+
+    print_response(client, &decoded)?;
     Ok(())
 }
 
@@ -405,23 +507,16 @@ pub async fn handle_decode(params: ParamsWalletDecode) -> Result<(), Box<dyn std
 ///
 /// # Arguments
 ///
+/// * `client` - client used to call the headless
 /// * `params` - arguments to configure the call being made
 ///
 pub async fn handle_tx_confirmation(
+    client: &HeadlessClient,
     params: ParamsWalletTxConfirmation,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, "/wallet/tx-confirmation-blocks")?;
-
-    let text_response = build_client(&params.config)?
-        .get(url)
-        .header("X-Wallet-Id", params.wallet_id)
-        .query(&[("id", params.id)])
-        .send()
-        .await?
-        .text()
-        .await?;
+) -> Result<(), HeadlessCliError> {
+    let response = client.tx_confirmation(&params).await?;
 
-    println!("{}", text_response);
+    print_response(client, &response)?;
     Ok(())
 }
 
@@ -429,139 +524,229 @@ pub async fn handle_tx_confirmation(
 ///
 /// # Arguments
 ///
+/// * `client` - client used to call the headless
 /// * `params` - arguments to configure the call being made
+/// * `wait` - wait for the transaction to be confirmed
 ///
 pub async fn handle_simple_send(
+    client: &HeadlessClient,
     params: ParamsWalletSimpleSend,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, "/wallet/simple-send-tx")?;
-
-    let mut map: HashMap<&str, HashMapValue> = HashMap::new();
-    map.insert("address", params.address.into());
-    map.insert("value", params.value.into());
+    wait: Option<ParamsWaitConfirmations>,
+) -> Result<(), HeadlessCliError> {
+    let output = SendOutput::Value {
+        address: params.address.clone(),
+        value: params.value,
+        token: params.token.clone(),
+        timelock: None,
+    };
+    let body = build_send_tx_body(&[output], &[], params.change_address.as_deref())?;
+    check_funds(client, &params.wallet_id, &body).await?;
+
+    if confirmation_required(client)? {
+        let lines = [
+            output_line(
+                client,
+                params.value,
+                params.token.as_deref(),
+                Some(&params.address),
+            )
+            .await,
+            change_line(params.change_address.as_deref()),
+        ];
+        confirm("send", &lines)?;
+    }
+
+    let result = client.simple_send(&params).await;
+    let response = with_preview(client, &params.wallet_id, &body, result).await?;
+    wait_confirmations(client, &params.wallet_id, &response.hash, wait).await?;
+
+    print_response(client, &response)?;
+    Ok(())
+}
 
-    if let Some(change_address) = params.change_address {
-        map.insert("change_address", change_address.into());
+/// Confirmation lines of a `send-tx` body
+async fn send_tx_lines(
+    client: &HeadlessClient,
+    body: &str,
+) -> Result<Vec<String>, HeadlessCliError> {
+    let body: serde_json::Value = serde_json::from_str(body)?;
+    let mut lines = vec![];
+
+    let outputs = body["outputs"].as_array().cloned().unwrap_or_default();
+    for output in outputs.iter() {
+        if output["type"] == "data" {
+            lines.push(format!(
+                "data output: {}",
+                output["data"].as_str().unwrap_or("")
+            ));
+            continue;
+        }
+        let value = output["value"].as_u64().unwrap_or(0);
+        lines.push(
+            output_line(
+                client,
+                value,
+                output["token"].as_str(),
+                output["address"].as_str(),
+            )
+            .await,
+        );
     }
-
-    if let Some(token) = params.token {
-        map.insert("token", token.into());
+    if let Some(inputs) = body["inputs"].as_array() {
+        lines.push(format!("spending {} chosen inputs", inputs.len()));
     }
+    lines.push(change_line(body["change_address"].as_str()));
+    Ok(lines)
+}
 
-    let text_response = build_client(&params.config)?
-        .post(url)
-        .header("X-Wallet-Id", params.wallet_id)
-        .json(&map)
-        .send()
-        .await?
-        .text()
-        .await?;
+/// On a dry run, add the decoded transaction that `body` would send to the request.
+///
+/// The preview is best effort, the request is still printed if the headless
+/// cannot build the transaction without pushing it (only P2SH wallets can).
+/// Other commands that send a transaction use [`without_preview`].
+async fn with_preview(
+    client: &HeadlessClient,
+    wallet_id: &str,
+    body: &str,
+    result: Result<SendTxResponse, HeadlessCliError>,
+) -> Result<SendTxResponse, HeadlessCliError> {
+    match result {
+        Err(HeadlessCliError::DryRun(mut request)) => {
+            match client.preview_send_tx(wallet_id, body).await {
+                Ok(decoded) => request.preview = Some(serde_json::to_value(decoded.tx)?),
+                Err(err) => eprintln!(
+                    "preview unavailable, only P2SH wallets can build a transaction without sending it: {}",
+                    err
+                ),
+            }
+            Err(HeadlessCliError::DryRun(request))
+        }
+        result => result,
+    }
+}
 
-    println!("{}", text_response);
-    Ok(())
+/// On a dry run, note that the transaction of `command` cannot be previewed.
+fn without_preview<T>(
+    command: &str,
+    result: Result<T, HeadlessCliError>,
+) -> Result<T, HeadlessCliError> {
+    if let Err(HeadlessCliError::DryRun(_)) = &result {
+        eprintln!(
+            "preview unavailable, the headless cannot build a {} transaction without sending it",
+            command
+        );
+    }
+    result
 }
 
 /// Send a transaction as specified in the given body.
 ///
 /// # Arguments
 ///
+/// * `client` - client used to call the headless
 /// * `params` - arguments to configure the call being made
+/// * `wait` - wait for the transaction to be confirmed
 ///
-pub async fn handle_send(params: ParamsWalletSend) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, "/wallet/send-tx")?;
-
-    let text_response = build_client(&params.config)?
-        .post(url)
-        .header("X-Wallet-Id", params.wallet_id)
-        .header("Content-Type", "application/json")
-        .body(params.body)
-        .send()
-        .await?
-        .text()
-        .await?;
+pub async fn handle_send(
+    client: &HeadlessClient,
+    params: ParamsWalletSend,
+    wait: Option<ParamsWaitConfirmations>,
+) -> Result<(), HeadlessCliError> {
+    check_funds(client, &params.wallet_id, &params.body).await?;
 
-    println!("{}", text_response);
+    if confirmation_required(client)? {
+        confirm("send", &send_tx_lines(client, &params.body).await?)?;
+    }
+
+    let result = client.send_tx(&params).await;
+    let response = with_preview(client, &params.wallet_id, &params.body, result).await?;
+    wait_confirmations(client, &params.wallet_id, &response.hash, wait).await?;
+
+    print_response(client, &response)?;
     Ok(())
 }
 
-/// Create a custom token in the given wallet.
+/// Pay every row of a csv or json file, grouping them in as few transactions as possible.
 ///
 /// # Arguments
 ///
+/// * `client` - client used to call the headless
 /// * `params` - arguments to configure the call being made
 ///
-pub async fn handle_create_token(
-    params: ParamsWalletCreateToken,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, "/wallet/create-token")?;
-
-    let mut map: HashMap<&str, HashMapValue> = HashMap::new();
-    map.insert("name", params.name.into());
-    map.insert("symbol", params.symbol.into());
-    map.insert("amount", params.amount.into());
-
-    if let Some(address) = params.address {
-        map.insert("address", address.into());
-    }
-
-    if let Some(change_address) = params.change_address {
-        map.insert("change_address", change_address.into());
-    }
-
-    if let Some(create_mint) = params.create_mint {
-        map.insert("create_mint", create_mint.into());
-    }
-
-    if let Some(mint_authority_address) = params.mint_authority_address {
-        map.insert("mint_authority_address", mint_authority_address.into());
-    }
-
-    if let Some(allow_external_mint_authority_address) =
-        params.allow_external_mint_authority_address
-    {
-        map.insert(
-            "allow_external_mint_authority_address",
-            allow_external_mint_authority_address.into(),
-        );
-    }
-
-    if let Some(create_melt) = params.create_melt {
-        map.insert("create_melt", create_melt.into());
-    }
+pub async fn handle_batch_send(
+    client: &HeadlessClient,
+    params: ParamsWalletBatchSend,
+) -> Result<(), HeadlessCliError> {
+    let content = read_input_file(&params.file)?;
+    let json = params.file.extension().is_some_and(|ext| ext == "json");
+    let config = client.config();
+    let rows = batch::parse_rows(&content, json, config.raw_units, config.network)?;
+
+    let results_path = params
+        .results
+        .clone()
+        .unwrap_or_else(|| batch::default_results_path(&params.file));
+    let mut results =
+        batch::BatchResults::load_or_new(&results_path, &params.file, rows, params.retry_unknown)?;
+
+    let max_outputs = match params.max_outputs {
+        Some(max_outputs) => max_outputs,
+        None => client
+            .status(&params.wallet_id)
+            .await?
+            .server_info
+            .and_then(|info| info.max_number_outputs)
+            .map_or(batch::DEFAULT_MAX_OUTPUTS, |max| max as usize),
+    };
 
-    if let Some(melt_authority_address) = params.melt_authority_address {
-        map.insert("melt_authority_address", melt_authority_address.into());
-    }
+    let summary = batch::send_batch(
+        client,
+        &params.wallet_id,
+        &mut results,
+        &results_path,
+        max_outputs,
+        params.change_address.as_deref(),
+        |rows, response| {
+            let first = rows.first().unwrap_or(&0);
+            let last = rows.last().unwrap_or(&0);
+            match response {
+                Ok(response) => eprintln!("rows {}-{}: sent {}", first, last, response.hash),
+                Err(err) => eprintln!("rows {}-{}: {}", first, last, err),
+            }
+        },
+    )
+    .await?;
 
-    if let Some(allow_external_melt_authority_address) =
-        params.allow_external_melt_authority_address
-    {
-        map.insert(
-            "allow_external_melt_authority_address",
-            allow_external_melt_authority_address.into(),
-        );
-    }
+    print_response(client, &summary)?;
 
-    if let Some(data) = params.data {
-        map.insert(
-            "data",
-            data.iter()
-                .map(|s| s.clone().into())
-                .collect::<Vec<HashMapValue>>()
-                .into(),
-        );
+    if summary.failed > 0 {
+        // The summary was printed already
+        return Err(HeadlessCliError::PartialFailure(format!(
+            "{} rows failed, see {} and run again to retry them",
+            summary.failed,
+            summary.results.display()
+        )));
     }
+    Ok(())
+}
 
-    let text_response = build_client(&params.config)?
-        .post(url)
-        .header("X-Wallet-Id", params.wallet_id)
-        .json(&map)
-        .send()
-        .await?
-        .text()
-        .await?;
+/// Create a custom token in the given wallet.
+///
+/// # Arguments
+///
+/// * `client` - client used to call the headless
+/// * `params` - arguments to configure the call being made
+/// * `wait` - wait for the transaction to be confirmed
+///
+pub async fn handle_create_token(
+    client: &HeadlessClient,
+    params: ParamsWalletCreateToken,
+    wait: Option<ParamsWaitConfirmations>,
+) -> Result<(), HeadlessCliError> {
+    let response = without_preview("create-token", client.create_token(&params).await)?;
+    wait_confirmations(client, &params.wallet_id, &response.hash, wait).await?;
 
-    println!("{}", text_response);
+    print_response(client, &response)?;
     Ok(())
 }
 
@@ -569,62 +754,19 @@ pub async fn handle_create_token(
 ///
 /// # Arguments
 ///
+/// * `client` - client used to call the headless
 /// * `params` - arguments to configure the call being made
+/// * `wait` - wait for the transaction to be confirmed
 ///
 pub async fn handle_mint_tokens(
+    client: &HeadlessClient,
     params: ParamsWalletMintTokens,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, "/wallet/mint-tokens")?;
-
-    let mut map: HashMap<&str, HashMapValue> = HashMap::new();
-    map.insert("token", params.token.into());
-    map.insert("amount", params.amount.into());
-
-    if let Some(address) = params.address {
-        map.insert("address", address.into());
-    }
-
-    if let Some(change_address) = params.change_address {
-        map.insert("change_address", change_address.into());
-    }
-
-    if let Some(mint_authority_address) = params.mint_authority_address {
-        map.insert("mint_authority_address", mint_authority_address.into());
-    }
+    wait: Option<ParamsWaitConfirmations>,
+) -> Result<(), HeadlessCliError> {
+    let response = without_preview("mint", client.mint_tokens(&params).await)?;
+    wait_confirmations(client, &params.wallet_id, &response.hash, wait).await?;
 
-    if let Some(allow_external_mint_authority_address) =
-        params.allow_external_mint_authority_address
-    {
-        map.insert(
-            "allow_external_mint_authority_address",
-            allow_external_mint_authority_address.into(),
-        );
-    }
-
-    if let Some(unshift_data) = params.unshift_data {
-        map.insert("unshift_data", unshift_data.into());
-    }
-
-    if let Some(data) = params.data {
-        map.insert(
-            "data",
-            data.iter()
-                .map(|s| s.clone().into())
-                .collect::<Vec<HashMapValue>>()
-                .into(),
-        );
-    }
-
-    let text_response = build_client(&params.config)?
-        .post(url)
-        .header("X-Wallet-Id", params.wallet_id)
-        .json(&map)
-        .send()
-        .await?
-        .text()
-        .await?;
-
-    println!("{}", text_response);
+    print_response(client, &response)?;
     Ok(())
 }
 
@@ -632,62 +774,56 @@ pub async fn handle_mint_tokens(
 ///
 /// # Arguments
 ///
+/// * `client` - client used to call the headless
 /// * `params` - arguments to configure the call being made
+/// * `wait` - wait for the transaction to be confirmed
 ///
 pub async fn handle_melt_tokens(
+    client: &HeadlessClient,
     params: ParamsWalletMeltTokens,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, "/wallet/melt-tokens")?;
-
-    let mut map: HashMap<&str, HashMapValue> = HashMap::new();
-    map.insert("token", params.token.into());
-    map.insert("amount", params.amount.into());
-
-    if let Some(address) = params.address {
-        map.insert("address", address.into());
-    }
-
-    if let Some(change_address) = params.change_address {
-        map.insert("change_address", change_address.into());
-    }
-
-    if let Some(melt_authority_address) = params.melt_authority_address {
-        map.insert("melt_authority_address", melt_authority_address.into());
-    }
-
-    if let Some(allow_external_melt_authority_address) =
-        params.allow_external_melt_authority_address
-    {
-        map.insert(
-            "allow_external_melt_authority_address",
-            allow_external_melt_authority_address.into(),
-        );
-    }
-
-    if let Some(unshift_data) = params.unshift_data {
-        map.insert("unshiftData", unshift_data.into());
-    }
-
-    if let Some(data) = params.data {
-        map.insert(
-            "data",
-            data.iter()
-                .map(|s| s.clone().into())
-                .collect::<Vec<HashMapValue>>()
-                .into(),
-        );
+    wait: Option<ParamsWaitConfirmations>,
+) -> Result<(), HeadlessCliError> {
+    if confirmation_required(client)? {
+        let symbol = token_symbol(client, Some(&params.token)).await;
+        let mut lines = vec![format!(
+            "melt {} {}",
+            Amount::from_units(params.amount),
+            symbol
+        )];
+        if let Some(address) = &params.address {
+            lines.push(format!("address {}", address));
+        }
+        lines.push(format!(
+            "HTR deposit returned to {}",
+            params
+                .deposit_address
+                .as_deref()
+                .unwrap_or("an address of the wallet")
+        ));
+        lines.push(format!(
+            "{} change to {}",
+            symbol,
+            params
+                .change_address
+                .as_deref()
+                .unwrap_or("an address of the wallet")
+        ));
+        if let Some(address) = &params.melt_authority_address {
+            lines.push(format!("melt authority to {}", address));
+        }
+        if params.allow_external_melt_authority_address == Some(true) {
+            lines.push(
+                "WARNING: the melt authority may be sent to an address outside the wallet"
+                    .to_string(),
+            );
+        }
+        confirm("melt tokens", &lines)?;
     }
 
-    let text_response = build_client(&params.config)?
-        .post(url)
-        .header("X-Wallet-Id", params.wallet_id)
-        .json(&map)
-        .send()
-        .await?
-        .text()
-        .await?;
+    let response = without_preview("melt", client.melt_tokens(&params).await)?;
+    wait_confirmations(client, &params.wallet_id, &response.hash, wait).await?;
 
-    println!("{}", text_response);
+    print_response(client, &response)?;
     Ok(())
 }
 
@@ -695,53 +831,16 @@ pub async fn handle_melt_tokens(
 ///
 /// # Arguments
 ///
+/// * `client` - client used to call the headless
 /// * `params` - arguments to configure the call being made
 ///
 pub async fn handle_utxo_filter(
+    client: &HeadlessClient,
     params: ParamsWalletUtxoFilter,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, "/wallet/utxo-filter")?;
-
-    let mut map: HashMap<&str, HashMapValue> = HashMap::new();
-
-    if let Some(max_utxos) = params.max_utxos {
-        map.insert("max_utxos", max_utxos.into());
-    }
-
-    if let Some(token) = params.token {
-        map.insert("token", token.into());
-    }
-
-    if let Some(filter_address) = params.filter_address {
-        map.insert("filter_address", filter_address.into());
-    }
+) -> Result<(), HeadlessCliError> {
+    let response = client.utxo_filter(&params).await?;
 
-    if let Some(amount_smaller_than) = params.amount_smaller_than {
-        map.insert("amount_smaller_than", amount_smaller_than.into());
-    }
-
-    if let Some(amount_bigger_than) = params.amount_bigger_than {
-        map.insert("amount_bigger_than", amount_bigger_than.into());
-    }
-
-    if let Some(maximum_amount) = params.maximum_amount {
-        map.insert("maximum_amount", maximum_amount.into());
-    }
-
-    if let Some(only_available_utxos) = params.only_available_utxos {
-        map.insert("only_available_utxos", only_available_utxos.into());
-    }
-
-    let text_response = build_client(&params.config)?
-        .post(url)
-        .header("X-Wallet-Id", params.wallet_id)
-        .json(&map)
-        .send()
-        .await?
-        .text()
-        .await?;
-
-    println!("{}", text_response);
+    print_response(client, &response)?;
     Ok(())
 }
 
@@ -749,49 +848,36 @@ pub async fn handle_utxo_filter(
 ///
 /// # Arguments
 ///
+/// * `client` - client used to call the headless
 /// * `params` - arguments to configure the call being made
+/// * `wait` - wait for the transaction to be confirmed
 ///
 pub async fn handle_utxo_consolidation(
+    client: &HeadlessClient,
     params: ParamsWalletUtxoConsolidation,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, "/wallet/utxo-consolidation")?;
-
-    let mut map: HashMap<&str, HashMapValue> = HashMap::new();
-
-    if let Some(max_utxos) = params.max_utxos {
-        map.insert("max_utxos", max_utxos.into());
-    }
-
-    if let Some(token) = params.token {
-        map.insert("token", token.into());
-    }
-
-    if let Some(filter_address) = params.filter_address {
-        map.insert("filter_address", filter_address.into());
-    }
-
-    if let Some(amount_smaller_than) = params.amount_smaller_than {
-        map.insert("amount_smaller_than", amount_smaller_than.into());
-    }
-
-    if let Some(amount_bigger_than) = params.amount_bigger_than {
-        map.insert("amount_bigger_than", amount_bigger_than.into());
-    }
-
-    if let Some(maximum_amount) = params.maximum_amount {
-        map.insert("maximum_amount", maximum_amount.into());
+    wait: Option<ParamsWaitConfirmations>,
+) -> Result<(), HeadlessCliError> {
+    if confirmation_required(client)? {
+        let mut lines = vec![format!(
+            "consolidate {} utxos to an address of the wallet",
+            token_symbol(client, params.token.as_deref()).await
+        )];
+        if let Some(filter_address) = &params.filter_address {
+            lines.push(format!("only utxos of {}", filter_address));
+        }
+        if let Some(max_utxos) = params.max_utxos {
+            lines.push(format!("at most {} utxos", max_utxos));
+        }
+        if let Some(maximum_amount) = params.maximum_amount {
+            lines.push(format!("at most {}", Amount::from_units(maximum_amount)));
+        }
+        confirm("consolidate utxos", &lines)?;
     }
 
-    let text_response = build_client(&params.config)?
-        .post(url)
-        .header("X-Wallet-Id", params.wallet_id)
-        .json(&map)
-        .send()
-        .await?
-        .text()
-        .await?;
+    let response = without_preview("consolidation", client.utxo_consolidation(&params).await)?;
+    wait_confirmations(client, &params.wallet_id, &response.tx_id, wait).await?;
 
-    println!("{}", text_response);
+    print_response(client, &response)?;
     Ok(())
 }
 
@@ -799,71 +885,19 @@ pub async fn handle_utxo_consolidation(
 ///
 /// # Arguments
 ///
+/// * `client` - client used to call the headless
 /// * `params` - arguments to configure the call being made
+/// * `wait` - wait for the transaction to be confirmed
 ///
 pub async fn handle_create_nft(
+    client: &HeadlessClient,
     params: ParamsWalletCreateNft,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, "/wallet/create-nft")?;
+    wait: Option<ParamsWaitConfirmations>,
+) -> Result<(), HeadlessCliError> {
+    let response = without_preview("create-nft", client.create_nft(&params).await)?;
+    wait_confirmations(client, &params.wallet_id, &response.hash, wait).await?;
 
-    let mut map: HashMap<&str, HashMapValue> = HashMap::new();
-    map.insert("name", params.name.into());
-    map.insert("symbol", params.symbol.into());
-    map.insert("data", params.data.into());
-    map.insert("amount", params.amount.into());
-
-    if let Some(address) = params.address {
-        map.insert("address", address.into());
-    }
-
-    if let Some(change_address) = params.change_address {
-        map.insert("change_address", change_address.into());
-    }
-
-    if let Some(create_mint) = params.create_mint {
-        map.insert("create_mint", create_mint.into());
-    }
-
-    if let Some(mint_authority_address) = params.mint_authority_address {
-        map.insert("mint_authority_address", mint_authority_address.into());
-    }
-
-    if let Some(allow_external_mint_authority_address) =
-        params.allow_external_mint_authority_address
-    {
-        map.insert(
-            "allow_external_mint_authority_address",
-            allow_external_mint_authority_address.into(),
-        );
-    }
-
-    if let Some(create_melt) = params.create_melt {
-        map.insert("create_melt", create_melt.into());
-    }
-
-    if let Some(melt_authority_address) = params.melt_authority_address {
-        map.insert("melt_authority_address", melt_authority_address.into());
-    }
-
-    if let Some(allow_external_melt_authority_address) =
-        params.allow_external_melt_authority_address
-    {
-        map.insert(
-            "allow_external_melt_authority_address",
-            allow_external_melt_authority_address.into(),
-        );
-    }
-
-    let text_response = build_client(&params.config)?
-        .post(url)
-        .header("X-Wallet-Id", params.wallet_id)
-        .json(&map)
-        .send()
-        .await?
-        .text()
-        .await?;
-
-    println!("{}", text_response);
+    print_response(client, &response)?;
     Ok(())
 }
 
@@ -871,73 +905,34 @@ pub async fn handle_create_nft(
 ///
 /// # Arguments
 ///
+/// * `client` - client used to call the headless
 /// * `params` - arguments to configure the call being made
 ///
-pub async fn handle_stop(params: ParamsWalletStop) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, "/wallet/stop")?;
-
-    let text_response = build_client(&params.config)?
-        .post(url)
-        .header("X-Wallet-Id", params.wallet_id)
-        .send()
-        .await?
-        .text()
-        .await?;
+pub async fn handle_stop(
+    client: &HeadlessClient,
+    params: ParamsWalletStop,
+) -> Result<(), HeadlessCliError> {
+    let response = client.stop(&params).await?;
 
-    println!("{}", text_response);
+    print_response(client, &response)?;
     Ok(())
 }
 
 pub async fn handle_list_tokens(
+    client: &HeadlessClient,
     params: ParamsCustomListTokens,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, "/wallet/tx-history")?;
-
-    let mut tokens = HashSet::new();
+) -> Result<(), HeadlessCliError> {
+    let tokens = client.list_tokens(&params).await?;
 
-    let tx_history = build_client(&params.config)?
-        .get(url)
-        .header("X-Wallet-Id", params.wallet_id.clone())
-        .send()
-        .await?
-        .json::<Vec<HistoryTx>>()
-        .await?;
-
-    let mut addresses = get_addresses(params.config.clone(), params.wallet_id.clone()).await?;
-    let known_addresses: HashSet<String> = addresses.drain(..).collect();
-
-    for tx in tx_history.iter() {
-        // Find tokens in the outputs
-        for output in tx.outputs.iter() {
-            if let Some(address) = output.decoded.address.clone() {
-                if known_addresses.contains(&address) {
-                    // Address is mine, so the token is mine also
-                    tokens.insert(output.token.clone());
-                }
-            }
-        }
-
-        for input in tx.inputs.iter() {
-            if let Some(address) = input.decoded.address.clone() {
-                if known_addresses.contains(&address) {
-                    // Address is mine, so the token is mine also
-                    tokens.insert(input.token.clone());
-                }
-            }
-        }
-    }
-
-    debug!("Found {} tokens.", tokens.len());
-
-    let tokens_json = json!(tokens);
-    println!("{}", tokens_json);
+    print_response(client, &tokens)?;
     Ok(())
 }
 
 pub async fn handle_custom_curl(
+    client: &HeadlessClient,
     params: ParamsCustomCurl,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, params.path.as_str())?;
+) -> Result<(), HeadlessCliError> {
+    let url = headless_cli::utils::build_headless_url(&client.config().host, params.path.as_str())?;
 
     let method = if params.post {
         if params.data {
@@ -948,11 +943,12 @@ pub async fn handle_custom_curl(
     } else {
         ""
     };
-    let mut headers_map = HashMap::<&str, String>::new();
-    headers_map.insert("X-Wallet-Id", params.wallet_id);
+    let mut headers_map = HashMap::<String, String>::new();
+    headers_map.extend(client.config().request_headers()?);
+    headers_map.insert("X-Wallet-Id".to_string(), params.wallet_id);
 
     if params.post && params.data {
-        headers_map.insert("Content-Type", "application/json".to_string());
+        headers_map.insert("Content-Type".to_string(), "application/json".to_string());
     }
 
     let headers = headers_map
@@ -966,356 +962,444 @@ pub async fn handle_custom_curl(
 }
 
 pub async fn handle_p2sh_txproposal_build(
+    client: &HeadlessClient,
     params: ParamsP2shTxProposalBuild,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, "/wallet/p2sh/tx-proposal")?;
-
-    let text_response = build_client(&params.config)?
-        .post(url)
-        .header("X-Wallet-Id", params.wallet_id)
-        .header("Content-Type", "application/json")
-        .body(params.body)
-        .send()
-        .await?
-        .text()
-        .await?;
+) -> Result<(), HeadlessCliError> {
+    check_funds(client, &params.wallet_id, &params.body).await?;
 
-    println!("{}", text_response);
+    let response = client.p2sh_txproposal_build(&params).await?;
+
+    print_response(client, &response)?;
     Ok(())
 }
 
 pub async fn handle_p2sh_txproposal_build_simple_send_tokens(
+    client: &HeadlessClient,
     params: ParamsP2shTxProposalBuildSimpleSendTokens,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, "/wallet/p2sh/tx-proposal")?;
-
-    let mut output = HashMap::<String, HashMapValue>::new();
-    output.insert(String::from("address"), params.address.into());
-    output.insert(String::from("value"), params.value.into());
-    if let Some(token) = params.token {
-        output.insert(String::from("token"), token.into());
-    }
-    let outputs = vec![HashMapValue::Dict(output)];
-    let mut map: HashMap<&str, HashMapValue> = HashMap::new();
-    map.insert("outputs", outputs.into());
+) -> Result<(), HeadlessCliError> {
+    let output = SendOutput::Value {
+        address: params.address.clone(),
+        value: params.value,
+        token: params.token.clone(),
+        timelock: None,
+    };
+    let body = build_send_tx_body(&[output], &[], params.change_address.as_deref())?;
+    check_funds(client, &params.wallet_id, &body).await?;
 
-    if let Some(change_address) = params.change_address {
-        map.insert("change_address", change_address.into());
-    }
-    if let Some(mark_inputs_as_used) = params.mark_inputs_as_used {
-        map.insert("mark_inputs_as_used", mark_inputs_as_used.into());
+    let response = client
+        .p2sh_txproposal_build_simple_send_tokens(&params)
+        .await?;
+
+    print_response(client, &response)?;
+    Ok(())
+}
+
+/// Confirmation lines of the inputs of a decoded transaction
+async fn input_lines(client: &HeadlessClient, tx: &DecodedTx) -> Vec<String> {
+    let mut lines = vec![];
+    for input in tx.inputs.iter() {
+        let mut line = format!("input {}:{}", input.tx_id, input.index);
+        if let Some(value) = input.value {
+            let address = input
+                .decoded
+                .as_ref()
+                .and_then(|decoded| decoded.address.as_deref());
+            line.push_str(&format!(
+                " ({} {} from {})",
+                Amount::from_units(value),
+                token_symbol(client, input.token.as_deref()).await,
+                address.unwrap_or("unknown address")
+            ));
+        }
+        if input.mine == Some(true) {
+            line.push_str(" (this wallet)");
+        }
+        lines.push(line);
     }
+    lines
+}
 
-    let text_response = build_client(&params.config)?
-        .post(url)
-        .header("X-Wallet-Id", params.wallet_id)
-        .header("Content-Type", "application/json")
-        .json(&map)
-        .send()
-        .await?
-        .text()
-        .await?;
+/// Confirmation lines of the outputs of a decoded transaction
+async fn output_lines(client: &HeadlessClient, tx: &DecodedTx) -> Vec<String> {
+    let mut lines = vec![];
+    for output in tx.outputs.iter() {
+        let address = output
+            .decoded
+            .as_ref()
+            .and_then(|decoded| decoded.address.as_deref());
+        let mut line = output_line(client, output.value, tx.output_token(output), address).await;
+        if output.mine == Some(true) {
+            line.push_str(" (this wallet)");
+        }
+        lines.push(line);
+    }
+    lines
+}
 
-    println!("{}", text_response);
+/// Decode a tx proposal before signing it, check it against the policy file
+/// and ask the user to confirm the inputs and outputs.
+async fn review_proposal(
+    client: &HeadlessClient,
+    wallet_id: &str,
+    tx_hex: &str,
+    policy: Option<&Path>,
+    action: &str,
+) -> Result<(), HeadlessCliError> {
+    let required = confirmation_required(client)?;
+    if policy.is_none() && !required {
+        return Ok(());
+    }
+
+    let decode = ParamsWalletDecode {
+        wallet_id: wallet_id.to_string(),
+        tx_hex: Some(tx_hex.to_string()),
+        partial_tx: None,
+    };
+    let tx = client.decode(&decode).await?.tx;
+    if let Some(path) = policy {
+        OutputPolicy::load(path, client.config().raw_units)?.check(&tx)?;
+    }
+    if required {
+        let mut lines = input_lines(client, &tx).await;
+        lines.extend(output_lines(client, &tx).await);
+        confirm(action, &lines)?;
+    }
     Ok(())
 }
 
+/// Get this wallet signatures for a tx proposal, after reviewing it.
+///
+/// # Arguments
+///
+/// * `client` - client used to call the headless
+/// * `params` - arguments to configure the call being made
+/// * `policy` - file with the outputs expected on the proposal
+///
 pub async fn handle_p2sh_txproposal_get_my_signatures(
+    client: &HeadlessClient,
     params: ParamsP2shTxProposalGetMySignatures,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(
-        &params.config.host,
-        "/wallet/p2sh/tx-proposal/get-my-signatures",
-    )?;
-
-    let mut map: HashMap<&str, HashMapValue> = HashMap::new();
-    map.insert("txHex", params.tx_hex.into());
-
-    let text_response = build_client(&params.config)?
-        .post(url)
-        .header("X-Wallet-Id", params.wallet_id)
-        .json(&map)
-        .send()
-        .await?
-        .text()
-        .await?;
-
-    println!("{}", text_response);
+    policy: Option<&Path>,
+) -> Result<(), HeadlessCliError> {
+    review_proposal(
+        client,
+        &params.wallet_id,
+        &params.tx_hex,
+        policy,
+        "sign the transaction",
+    )
+    .await?;
+    let response = client.p2sh_txproposal_get_my_signatures(&params).await?;
+
+    print_response(client, &response)?;
     Ok(())
 }
 
 pub async fn handle_p2sh_txproposal_sign(
+    client: &HeadlessClient,
     params: ParamsP2shTxProposalSign,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, "/wallet/p2sh/tx-proposal/sign")?;
-
-    let mut map: HashMap<&str, HashMapValue> = HashMap::new();
-    map.insert("txHex", params.tx_hex.into());
-    map.insert(
-        "signatures",
-        HashMapValue::List(
-            params
-                .signatures
-                .iter()
-                .map(|s| HashMapValue::String(s.clone()))
-                .collect(),
-        ),
-    );
-
-    let text_response = build_client(&params.config)?
-        .post(url)
-        .header("X-Wallet-Id", params.wallet_id)
-        .json(&map)
-        .send()
-        .await?
-        .text()
-        .await?;
+) -> Result<(), HeadlessCliError> {
+    let response = client.p2sh_txproposal_sign(&params).await?;
 
-    println!("{}", text_response);
+    print_response(client, &response)?;
     Ok(())
 }
 
+/// Confirmation lines of the outputs of a tx proposal
+async fn sign_and_push_lines(
+    client: &HeadlessClient,
+    params: &ParamsP2shTxProposalSign,
+) -> Result<Vec<String>, HeadlessCliError> {
+    let decode = ParamsWalletDecode {
+        wallet_id: params.wallet_id.clone(),
+        tx_hex: Some(params.tx_hex.clone()),
+        partial_tx: None,
+    };
+    let tx = client.decode(&decode).await?.tx;
+    Ok(output_lines(client, &tx).await)
+}
+
 pub async fn handle_p2sh_txproposal_sign_and_push(
+    client: &HeadlessClient,
     params: ParamsP2shTxProposalSign,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(
-        &params.config.host,
-        "/wallet/p2sh/tx-proposal/sign-and-push",
-    )?;
-
-    let mut map: HashMap<&str, HashMapValue> = HashMap::new();
-    map.insert("txHex", HashMapValue::String(params.tx_hex));
-    map.insert(
-        "signatures",
-        HashMapValue::List(
-            params
-                .signatures
-                .iter()
-                .map(|s| HashMapValue::String(s.clone()))
-                .collect(),
-        ),
-    );
+    wait: Option<ParamsWaitConfirmations>,
+) -> Result<(), HeadlessCliError> {
+    if confirmation_required(client)? {
+        let lines = sign_and_push_lines(client, &params).await?;
+        confirm("sign and push the transaction", &lines)?;
+    }
 
-    let text_response = build_client(&params.config)?
-        .post(url)
-        .header("X-Wallet-Id", params.wallet_id)
-        .json(&map)
-        .send()
-        .await?
-        .text()
-        .await?;
+    let response = client.p2sh_txproposal_sign_and_push(&params).await?;
+    wait_confirmations(client, &params.wallet_id, &response.hash, wait).await?;
 
-    println!("{}", text_response);
+    print_response(client, &response)?;
     Ok(())
 }
 
-/// Create a custom token in the given P2SH wallet.
+/// Get the signatures of every signer wallet, then sign (and push) the proposal on the coordinator.
 ///
 /// # Arguments
 ///
+/// * `client` - client used to call the headless
 /// * `params` - arguments to configure the call being made
-///
-pub async fn handle_p2sh_txproposal_create_token(
-    params: ParamsWalletP2shTxProposalCreateToken,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, "/wallet/p2sh/tx-proposal/create-token")?;
-
-    let mut map: HashMap<&str, HashMapValue> = HashMap::new();
-    map.insert("name", HashMapValue::String(params.name));
-    map.insert("symbol", HashMapValue::String(params.symbol));
-    map.insert("amount", HashMapValue::Int(params.amount));
-
-    if let Some(address) = params.address {
-        map.insert("address", HashMapValue::String(address));
-    }
-
-    if let Some(change_address) = params.change_address {
-        map.insert("change_address", HashMapValue::String(change_address));
+/// * `policy` - file with the outputs expected on the proposal
+/// * `wait` - wait for the confirmations of the pushed transaction
+///
+pub async fn handle_p2sh_txproposal_collect(
+    client: &HeadlessClient,
+    params: ParamsP2shTxProposalCollect,
+    policy: Option<&Path>,
+    wait: Option<ParamsWaitConfirmations>,
+) -> Result<(), HeadlessCliError> {
+    // Reviewed once here, the signer wallets sign without asking
+    let action = match params.push {
+        true => "sign and push the transaction",
+        false => "sign the transaction",
+    };
+    review_proposal(client, &params.wallet_id, &params.tx_hex, policy, action).await?;
+
+    let results = client
+        .p2sh_txproposal_collect_signatures(&params.tx_hex, &params.signer_wallets)
+        .await;
+
+    let mut signatures = vec![];
+    let mut first_error = None;
+    for (wallet_id, result) in params.signer_wallets.iter().zip(results) {
+        match result {
+            Ok(signature) => {
+                eprintln!("wallet {}: signed", wallet_id);
+                signatures.push(signature);
+            }
+            Err(err) => {
+                eprintln!("wallet {}: {}", wallet_id, err);
+                first_error.get_or_insert(err);
+            }
+        }
     }
-
-    if let Some(create_mint) = params.create_mint {
-        map.insert("create_mint", HashMapValue::Bool(create_mint));
+    if let Some(err) = first_error {
+        return Err(err);
     }
 
-    if let Some(mint_authority_address) = params.mint_authority_address {
-        map.insert(
-            "mint_authority_address",
-            HashMapValue::String(mint_authority_address),
-        );
+    let sign = ParamsP2shTxProposalSign {
+        wallet_id: params.wallet_id,
+        tx_hex: params.tx_hex,
+        signatures,
+    };
+    if params.push {
+        let response = client.p2sh_txproposal_sign_and_push(&sign).await?;
+        wait_confirmations(client, &sign.wallet_id, &response.hash, wait).await?;
+        print_response(client, &response)?;
+    } else {
+        let response = client.p2sh_txproposal_sign(&sign).await?;
+        print_response(client, &response)?;
     }
+    Ok(())
+}
 
-    if let Some(allow_external_mint_authority_address) =
-        params.allow_external_mint_authority_address
-    {
-        map.insert(
-            "allow_external_mint_authority_address",
-            HashMapValue::Bool(allow_external_mint_authority_address),
-        );
-    }
+/// Build (or take) a tx proposal and save it on a new proposal file.
+///
+/// # Arguments
+///
+/// * `client` - client used to call the headless
+/// * `params` - arguments to configure the call being made
+///
+pub async fn handle_p2sh_proposal_new(
+    client: &HeadlessClient,
+    params: ParamsP2shProposalNew,
+) -> Result<(), HeadlessCliError> {
+    if params.file.exists() {
+        return Err(HeadlessCliError::InvalidInput(format!(
+            "{} already exists",
+            params.file.display()
+        )));
+    }
+
+    let tx_hex = match (params.tx_hex, params.body) {
+        (Some(tx_hex), _) => tx_hex,
+        (None, Some(body)) => {
+            let build = ParamsP2shTxProposalBuild {
+                wallet_id: params.wallet_id.clone(),
+                body,
+            };
+            client.p2sh_txproposal_build(&build).await?.tx_hex
+        }
+        (None, None) => {
+            return Err(HeadlessCliError::InvalidInput(
+                "a tx hex or a tx proposal body is required".to_string(),
+            ))
+        }
+    };
+    let decode = ParamsWalletDecode {
+        wallet_id: params.wallet_id.clone(),
+        tx_hex: Some(tx_hex.clone()),
+        partial_tx: None,
+    };
+    let summary = client.decode(&decode).await?.tx;
 
-    if let Some(create_melt) = params.create_melt {
-        map.insert("create_melt", HashMapValue::Bool(create_melt));
-    }
+    let proposal = Proposal::new(tx_hex, summary, params.signatures_required, params.signers)?;
+    proposal.save(&params.file)?;
 
-    if let Some(melt_authority_address) = params.melt_authority_address {
-        map.insert(
-            "melt_authority_address",
-            HashMapValue::String(melt_authority_address),
-        );
-    }
+    print_response(client, &proposal.status())?;
+    Ok(())
+}
 
-    if let Some(allow_external_melt_authority_address) =
-        params.allow_external_melt_authority_address
-    {
-        map.insert(
-            "allow_external_melt_authority_address",
-            HashMapValue::Bool(allow_external_melt_authority_address),
-        );
-    }
+/// Add signatures to a proposal file, the signatures of this wallet if none are given.
+///
+/// # Arguments
+///
+/// * `client` - client used to call the headless
+/// * `params` - arguments to configure the call being made
+/// * `policy` - file with the outputs expected on the proposal
+///
+pub async fn handle_p2sh_proposal_add_signature(
+    client: &HeadlessClient,
+    params: ParamsP2shProposalAddSignature,
+    policy: Option<&Path>,
+) -> Result<(), HeadlessCliError> {
+    let mut proposal = Proposal::load(&params.file)?;
+
+    let signatures = match params.signatures {
+        Some(signatures) => signatures,
+        None => {
+            review_proposal(
+                client,
+                &params.wallet_id,
+                &proposal.tx_hex,
+                policy,
+                "sign the transaction",
+            )
+            .await?;
+            let get_my_signatures = ParamsP2shTxProposalGetMySignatures {
+                wallet_id: params.wallet_id.clone(),
+                tx_hex: proposal.tx_hex.clone(),
+            };
+            client
+                .p2sh_txproposal_get_my_signatures(&get_my_signatures)
+                .await?
+                .signatures
+        }
+    };
+    let pubkey = proposal.add_signatures(&signatures)?;
+    proposal.save(&params.file)?;
 
-    if let Some(mark_inputs_as_used) = params.mark_inputs_as_used {
-        map.insert(
-            "mark_inputs_as_used",
-            HashMapValue::Bool(mark_inputs_as_used),
-        );
-    }
+    let status = proposal.status();
+    eprintln!(
+        "signed by {} ({}/{} signatures)",
+        pubkey, status.collected, status.signatures_required
+    );
+    print_response(client, &status)?;
+    Ok(())
+}
 
-    let text_response = build_client(&params.config)?
-        .post(url)
-        .header("X-Wallet-Id", params.wallet_id)
-        .json(&map)
-        .send()
-        .await?
-        .text()
-        .await?;
+/// Show how many signatures a proposal file has, no request is made.
+pub fn handle_p2sh_proposal_status(
+    client: &HeadlessClient,
+    file: &Path,
+) -> Result<(), HeadlessCliError> {
+    let proposal = Proposal::load(file)?;
 
-    println!("{}", text_response);
+    print_response(client, &proposal.status())?;
     Ok(())
 }
 
+/// Sign the transaction of a proposal file with the collected signatures and optionally push it.
 ///
 /// # Arguments
 ///
+/// * `client` - client used to call the headless
 /// * `params` - arguments to configure the call being made
-///
-pub async fn handle_p2sh_txproposal_mint_tokens(
-    params: ParamsWalletP2shTxProposalMintTokens,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, "/wallet/p2sh/tx-proposal/mint-tokens")?;
-
-    let mut map: HashMap<&str, HashMapValue> = HashMap::new();
-    map.insert("token", HashMapValue::String(params.token));
-    map.insert("amount", HashMapValue::Int(params.amount));
-
-    if let Some(address) = params.address {
-        map.insert("address", HashMapValue::String(address));
-    }
+/// * `wait` - wait for the confirmations of the pushed transaction
+///
+pub async fn handle_p2sh_proposal_finalize(
+    client: &HeadlessClient,
+    params: ParamsP2shProposalFinalize,
+    wait: Option<ParamsWaitConfirmations>,
+) -> Result<(), HeadlessCliError> {
+    let mut proposal = Proposal::load(&params.file)?;
+    if let Some(tx_id) = &proposal.tx_id {
+        return Err(HeadlessCliError::InvalidInput(format!(
+            "the proposal was already pushed as {}",
+            tx_id
+        )));
+    }
+    if !proposal.is_ready() {
+        return Err(HeadlessCliError::InvalidInput(format!(
+            "the proposal has {} of the {} required signatures",
+            proposal.signatures.len(),
+            proposal.signatures_required
+        )));
+    }
+
+    let sign = ParamsP2shTxProposalSign {
+        wallet_id: params.wallet_id.clone(),
+        tx_hex: proposal.tx_hex.clone(),
+        signatures: proposal.collected_signatures(),
+    };
+    if !params.push {
+        let response = client.p2sh_txproposal_sign(&sign).await?;
+        proposal.signed_tx_hex = Some(response.tx_hex.clone());
+        proposal.save(&params.file)?;
 
-    if let Some(change_address) = params.change_address {
-        map.insert("change_address", HashMapValue::String(change_address));
+        print_response(client, &response)?;
+        return Ok(());
     }
 
-    if let Some(create_mint) = params.create_mint {
-        map.insert("create_mint", HashMapValue::Bool(create_mint));
+    if confirmation_required(client)? {
+        let lines = sign_and_push_lines(client, &sign).await?;
+        confirm("sign and push the transaction", &lines)?;
     }
+    let response = client.p2sh_txproposal_sign_and_push(&sign).await?;
+    proposal.tx_id = Some(response.hash.clone());
+    proposal.save(&params.file)?;
+    wait_confirmations(client, &params.wallet_id, &response.hash, wait).await?;
 
-    if let Some(mint_authority_address) = params.mint_authority_address {
-        map.insert(
-            "mint_authority_address",
-            HashMapValue::String(mint_authority_address),
-        );
-    }
+    print_response(client, &response)?;
+    Ok(())
+}
 
-    if let Some(allow_external_mint_authority_address) =
-        params.allow_external_mint_authority_address
-    {
-        map.insert(
-            "allow_external_mint_authority_address",
-            HashMapValue::Bool(allow_external_mint_authority_address),
-        );
-    }
+/// Create a custom token in the given P2SH wallet.
+///
+/// # Arguments
+///
+/// * `client` - client used to call the headless
+/// * `params` - arguments to configure the call being made
+///
+pub async fn handle_p2sh_txproposal_create_token(
+    client: &HeadlessClient,
+    params: ParamsWalletP2shTxProposalCreateToken,
+) -> Result<(), HeadlessCliError> {
+    let response = client.p2sh_txproposal_create_token(&params).await?;
 
-    if let Some(mark_inputs_as_used) = params.mark_inputs_as_used {
-        map.insert(
-            "mark_inputs_as_used",
-            HashMapValue::Bool(mark_inputs_as_used),
-        );
-    }
+    print_response(client, &response)?;
+    Ok(())
+}
 
-    let text_response = build_client(&params.config)?
-        .post(url)
-        .header("X-Wallet-Id", params.wallet_id)
-        .json(&map)
-        .send()
-        .await?
-        .text()
-        .await?;
+///
+/// # Arguments
+///
+/// * `client` - client used to call the headless
+/// * `params` - arguments to configure the call being made
+///
+pub async fn handle_p2sh_txproposal_mint_tokens(
+    client: &HeadlessClient,
+    params: ParamsWalletP2shTxProposalMintTokens,
+) -> Result<(), HeadlessCliError> {
+    let response = client.p2sh_txproposal_mint_tokens(&params).await?;
 
-    println!("{}", text_response);
+    print_response(client, &response)?;
     Ok(())
 }
 
 /// # Arguments
 ///
+/// * `client` - client used to call the headless
 /// * `params` - arguments to configure the call being made
 ///
 pub async fn handle_p2sh_txproposal_melt_tokens(
+    client: &HeadlessClient,
     params: ParamsWalletP2shTxProposalMeltTokens,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = build_headless_url(&params.config.host, "/wallet/p2sh/tx-proposal/melt-tokens")?;
-
-    let mut map: HashMap<&str, HashMapValue> = HashMap::new();
-    map.insert("token", HashMapValue::String(params.token));
-    map.insert("amount", HashMapValue::Int(params.amount));
-
-    if let Some(address) = params.address {
-        map.insert("address", HashMapValue::String(address));
-    }
-
-    if let Some(deposit_address) = params.deposit_address {
-        map.insert("deposit_address", HashMapValue::String(deposit_address));
-    }
-
-    if let Some(change_address) = params.change_address {
-        map.insert("change_address", HashMapValue::String(change_address));
-    }
-
-    if let Some(create_melt) = params.create_melt {
-        map.insert("create_melt", HashMapValue::Bool(create_melt));
-    }
-
-    if let Some(melt_authority_address) = params.melt_authority_address {
-        map.insert(
-            "melt_authority_address",
-            HashMapValue::String(melt_authority_address),
-        );
-    }
-
-    if let Some(allow_external_melt_authority_address) =
-        params.allow_external_melt_authority_address
-    {
-        map.insert(
-            "allow_external_melt_authority_address",
-            HashMapValue::Bool(allow_external_melt_authority_address),
-        );
-    }
-
-    if let Some(mark_inputs_as_used) = params.mark_inputs_as_used {
-        map.insert(
-            "mark_inputs_as_used",
-            HashMapValue::Bool(mark_inputs_as_used),
-        );
-    }
-
-    let text_response = build_client(&params.config)?
-        .post(url)
-        .header("X-Wallet-Id", params.wallet_id)
-        .json(&map)
-        .send()
-        .await?
-        .text()
-        .await?;
+) -> Result<(), HeadlessCliError> {
+    let response = client.p2sh_txproposal_melt_tokens(&params).await?;
 
-    println!("{}", text_response);
+    print_response(client, &response)?;
     Ok(())
 }
@@ -0,0 +1,19 @@
+//! Client library for the hathor headless wallet.
+//!
+//! The [`client::HeadlessClient`] exposes one method per headless endpoint,
+//! the `headless_cli` binary is a thin layer over it.
+
+pub mod amount;
+pub mod batch;
+pub mod client;
+pub mod config;
+pub mod data;
+pub mod error;
+pub mod output;
+pub mod params;
+pub mod proposal;
+pub mod query;
+pub mod send;
+pub mod tx;
+pub mod utils;
+pub mod validation;
@@ -1,26 +1,88 @@
-pub mod data;
-pub mod handler;
-mod methods;
-pub mod params;
-mod utils;
+mod handler;
 
 use handler::*;
-use params::*;
-
-use clap::{self, Parser, Subcommand};
+use headless_cli::amount::AmountArg;
+use headless_cli::client::HeadlessClient;
+use headless_cli::config::{ConfigFile, Profile};
+use headless_cli::error::HeadlessCliError;
+use headless_cli::output::OutputFormat;
+use headless_cli::params::*;
+use headless_cli::send::{build_send_tx_body, InputSpec, OutputSpec, SendOutput};
+use headless_cli::validation::{validate_address_network, Network, HTR_UID};
+
+use clap::{self, ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand};
 // use env_logger;
 use std::env;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
 // use log;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+const EXIT_CODES_HELP: &str = "Exit codes:
+  0  success
+  2  invalid input
+  3  could not connect to the headless
+  4  headless answered with a non-2xx status
+  5  headless answered with `success: false`
+  6  invalid response from the headless
+  7  invalid url
+  8  invalid config file or environment
+  9  timed out waiting for the wallet or a transaction, or for the response to a
+     request that changes the wallet (it may have been executed, check the history)
+  10 the transaction was voided while waiting for it
+  11 the command was not confirmed
+  12 the wallet balance does not cover the outputs (--check-balance)
+  13 the tx proposal does not match the signer policy (--policy)
+  14 some rows of a batch were rejected, the others were sent
+  15 internal error of the cli (a task panicked), please report it";
+
 /////////////////////////////////////////// CLI structure
 
 #[derive(Parser)]
-#[command(author = "r4mmer", version = VERSION, long_about = None)]
+#[command(author = "r4mmer", version = VERSION, long_about = None, after_help = EXIT_CODES_HELP)]
 struct Cli {
-    #[arg(long, global = true, default_value = "http://localhost:8000")]
-    host: String,
+    /// The headless url [env: HEADLESS_CLI_HOST] [default: http://localhost:8000]
+    #[arg(long, global = true)]
+    host: Option<String>,
+
+    /// Config file to read the profiles from [default: ~/.config/headless_cli/config.toml]
+    #[arg(long, global = true, env = "HEADLESS_CLI_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Profile of the config file to use (defaults to `default_profile` of the config file)
+    #[arg(long, global = true, env = "HEADLESS_CLI_PROFILE")]
+    profile: Option<String>,
+
+    /// PEM file with extra CA certificates to trust [env: HEADLESS_CLI_CA_CERT]
+    #[arg(long, global = true)]
+    ca_cert: Option<PathBuf>,
+
+    /// PEM file with the client certificate for mTLS [env: HEADLESS_CLI_CLIENT_CERT]
+    #[arg(long, global = true, requires = "client_key")]
+    client_cert: Option<PathBuf>,
+
+    /// PEM file with the PKCS#8 key of the client certificate [env: HEADLESS_CLI_CLIENT_KEY]
+    #[arg(long, global = true, requires = "client_cert")]
+    client_key: Option<PathBuf>,
+
+    /// Do not verify the headless certificate (DANGEROUS, for testing only)
+    #[arg(long, global = true)]
+    insecure: bool,
+
+    /// Timeout in seconds for each request, 0 disables it [env: HEADLESS_CLI_TIMEOUT] [default: 60]
+    #[arg(long, global = true)]
+    timeout: Option<u64>,
+
+    /// Retries of idempotent requests on connection errors and 502/503/504 [env: HEADLESS_CLI_RETRIES] [default: 3]
+    #[arg(long, global = true)]
+    retries: Option<u32>,
+
+    /// Also retry requests that change the wallet (a transaction may be sent twice)
+    #[arg(long, global = true)]
+    retry_mutating: bool,
 
     #[arg(
         long,
@@ -30,6 +92,39 @@ struct Cli {
     )]
     debug: bool,
 
+    /// Print the headless response even when it reports a failure (non-2xx status or `success: false`)
+    #[arg(long, global = true)]
+    allow_failure: bool,
+
+    /// Amounts are integers in the smallest unit of the token (e.g. `1234` for 12.34 HTR), without a symbol
+    #[arg(long, global = true)]
+    raw_units: bool,
+
+    /// Print the requests that would change the wallet instead of sending them. Only
+    /// simple-send and send on P2SH wallets also preview the transaction they would send
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    /// Reject addresses of other networks before sending any request [env: HEADLESS_CLI_NETWORK]
+    #[arg(long, global = true, value_enum)]
+    network: Option<Network>,
+
+    /// Check the wallet balance of every token before building or sending a transaction
+    #[arg(long, global = true)]
+    check_balance: bool,
+
+    /// Do not ask for confirmation before moving funds, required when stdin is not a terminal
+    #[arg(short, long, global = true)]
+    yes: bool,
+
+    /// Output format [default: table if stdout is a terminal, json otherwise]
+    #[arg(short, long, global = true, value_enum)]
+    output: Option<OutputFormat>,
+
+    /// Only print this field of the response, e.g. `available` or `utxos.0.amount`
+    #[arg(short, long, global = true)]
+    query: Option<String>,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -39,8 +134,8 @@ enum Commands {
     /// Start a wallet
     Start {
         /// Wallet id to use (all commands for this wallet will require this id)
-        #[arg(long, default_value = "default")]
-        wallet_id: String,
+        #[arg(long)]
+        wallet_id: Option<String>,
         /// Key of the seed (on `seeds` in the config)
         #[arg(long, default_value = "default")]
         seed_key: String,
@@ -68,6 +163,9 @@ enum Commands {
         /// Key of the multisig config (on `multisig` in the config)
         #[arg(long)]
         multisig_key: Option<String>,
+
+        #[command(flatten)]
+        wait: WaitReadyArgs,
     },
 
     /// Get the p2sh xpubkey of a configured seed (does not require a started wallet)
@@ -85,11 +183,23 @@ enum Commands {
         token: String,
     },
 
+    /// Decode an address offline, checking its checksum, type and network (see --network)
+    ValidateAddress {
+        /// Address (base58 encoded)
+        address: String,
+    },
+
+    /// Offline transaction tools (no request is made)
+    Tx {
+        #[command(subcommand)]
+        command: TxCommands,
+    },
+
     /// Wallet commands (requires a started wallet)
     Wallet {
-        /// Target wallet id
-        #[arg(short, long, global = true, default_value = "default")]
-        wallet_id: String,
+        /// Target wallet id (defaults to the profile wallet id or `default`)
+        #[arg(short, long, global = true)]
+        wallet_id: Option<String>,
 
         #[command(subcommand)]
         command: WalletCommands,
@@ -114,18 +224,56 @@ enum Commands {
     },
 }
 
+#[derive(Args)]
+struct WaitReadyArgs {
+    /// Wait until the wallet is ready (synced), showing the progress on stderr
+    #[arg(long)]
+    wait: bool,
+
+    /// Seconds to wait for the wallet to be ready
+    #[arg(long, default_value_t = 300, requires = "wait")]
+    wait_timeout: u64,
+}
+
+impl WaitReadyArgs {
+    fn timeout(&self) -> Option<Duration> {
+        self.wait.then(|| Duration::from_secs(self.wait_timeout))
+    }
+}
+
+#[derive(Args)]
+struct WaitConfirmationArgs {
+    /// Wait until the transaction is confirmed by this many blocks, showing the progress on stderr
+    #[arg(long)]
+    wait_confirmations: Option<u64>,
+
+    /// Seconds to wait for the confirmations
+    #[arg(long, default_value_t = 600, requires = "wait_confirmations")]
+    wait_timeout: u64,
+}
+
+impl WaitConfirmationArgs {
+    fn params(&self) -> Option<ParamsWaitConfirmations> {
+        self.wait_confirmations
+            .map(|confirmations| ParamsWaitConfirmations {
+                confirmations,
+                timeout: Duration::from_secs(self.wait_timeout),
+            })
+    }
+}
+
 #[derive(Subcommand)]
 enum CustomCommands {
     /// List all tokens on the wallet history
     ListTokens {
-        #[arg(short, long, default_value = "default")]
-        wallet_id: String,
+        #[arg(short, long)]
+        wallet_id: Option<String>,
     },
 
     /// Make an http request (simple helper)
     Curl {
-        #[arg(short, long, default_value = "default")]
-        wallet_id: String,
+        #[arg(short, long)]
+        wallet_id: Option<String>,
         #[arg(short, long, default_value_t = false, default_missing_value = "true")]
         post: bool,
         #[arg(short, long, default_value_t = false, default_missing_value = "true")]
@@ -134,6 +282,18 @@ enum CustomCommands {
     },
 }
 
+#[derive(Subcommand)]
+enum TxCommands {
+    /// Compute the tx id, funds hash, graph hash and sighash of a transaction
+    Hash {
+        /// Transaction (or block) hex
+        tx_hex: String,
+        /// Fail if the computed tx id is not this one (e.g. the id reported by the headless)
+        #[arg(long)]
+        expect: Option<String>,
+    },
+}
+
 #[derive(Subcommand)]
 enum HsmCommands {
     /// Start a Dinamo Networks HSM wallet (requires special configuration)
@@ -142,8 +302,11 @@ enum HsmCommands {
         hsm_key: String,
 
         /// Wallet id to use (all commands for this wallet will require this id)
-        #[arg(long, default_value = "default")]
-        wallet_id: String,
+        #[arg(long)]
+        wallet_id: Option<String>,
+
+        #[command(flatten)]
+        wait: WaitReadyArgs,
     },
 }
 
@@ -154,8 +317,11 @@ enum FireblocksCommands {
         xpub: String,
 
         /// Wallet id to use (all commands for this wallet will require this id)
-        #[arg(long, default_value = "default")]
-        wallet_id: String,
+        #[arg(long)]
+        wallet_id: Option<String>,
+
+        #[command(flatten)]
+        wait: WaitReadyArgs,
     },
 }
 
@@ -171,6 +337,13 @@ enum WalletCommands {
     /// Wallet status
     Status {},
 
+    /// Wait until the wallet is ready (synced), showing the progress on stderr
+    WaitReady {
+        /// Seconds to wait for the wallet to be ready
+        #[arg(long, default_value_t = 300)]
+        wait_timeout: u64,
+    },
+
     /// Fetch an address from the wallet
     Address {
         /// Get address on this derivation index
@@ -215,6 +388,10 @@ enum WalletCommands {
 
         #[arg(short, long)]
         partial_tx: Option<String>,
+
+        /// Decode the tx hex locally without calling the headless (requires --network)
+        #[arg(long, requires = "tx_hex", conflicts_with = "partial_tx")]
+        offline: bool,
     },
 
     /// Get number of blocks confirming this tx.
@@ -224,19 +401,61 @@ enum WalletCommands {
     SimpleSend {
         /// Address (base58 encoded)
         address: String,
-        /// Amount of tokens to send
-        value: u32,
+        /// Amount of tokens to send (e.g. `12.34` or `12.34HTR`)
+        value: AmountArg,
         #[arg(short, long)]
         change_address: Option<String>,
         /// Token UID (hex encoded)
         #[arg(short, long)]
         token: Option<String>,
+
+        #[command(flatten)]
+        wait: WaitConfirmationArgs,
     },
 
     /// Send a complex transaction
+    #[command(
+        after_help = "Example: wallet send --to WewDeXWyvHP7jJTs7tjLoQfoB72LLxJQqN:12.34 --to WmtWgtk5GxdcDKwjNwmXXn74nQWTPWhKfx:1:<token uid> --data \"payout #1\""
+    )]
     Send {
-        /// JSON encoded { outputs: [{address, value, token?, type?, data?, timelock?}, inputs?: {type, index, hash}], change_address }
-        body: String,
+        /// Send AMOUNT of TOKEN (defaults to HTR) to ADDRESS, optionally locked until TIMELOCK (unix timestamp). Repeat for more outputs
+        #[arg(long = "to", value_name = "ADDRESS:AMOUNT[:TOKEN][:TIMELOCK]", required_unless_present_any = ["data", "body"])]
+        outputs: Vec<OutputSpec>,
+        /// Add a data output with this text (costs 0.01 HTR). Repeat for more outputs
+        #[arg(long)]
+        data: Vec<String>,
+        /// Spend this utxo. Repeat for more inputs, the headless chooses the inputs if none are given
+        #[arg(long = "input", value_name = "TXID:INDEX")]
+        inputs: Vec<InputSpec>,
+        #[arg(short, long)]
+        change_address: Option<String>,
+        /// Send this JSON body as is, without validations:
+        /// { outputs: [{address, value, token?, type?, data?, timelock?}], inputs?: [{hash, index}], change_address? }
+        #[arg(long, conflicts_with_all = ["outputs", "data", "inputs", "change_address"])]
+        body: Option<String>,
+
+        #[command(flatten)]
+        wait: WaitConfirmationArgs,
+    },
+
+    /// Pay every row of a csv (`address,amount[,token][,timelock]`) or json file,
+    /// grouping the payouts in as few transactions as possible.
+    /// The status of each row is saved on a results file, running again resumes the payouts
+    BatchSend {
+        /// csv or json (`[{address, amount, token?, timelock?}]`) file with the payouts
+        #[arg(long)]
+        file: PathBuf,
+        /// Results file [default: <FILE>.results.json]
+        #[arg(long)]
+        results: Option<PathBuf>,
+        /// Maximum outputs of each transaction [default: the headless limit]
+        #[arg(long)]
+        max_outputs: Option<usize>,
+        #[arg(short, long)]
+        change_address: Option<String>,
+        /// Send again rows that may have been sent by an interrupted run (may pay them twice)
+        #[arg(long)]
+        retry_unknown: bool,
     },
 
     /// Send a transaction to create a new token
@@ -246,7 +465,7 @@ enum WalletCommands {
         /// Token symbol
         symbol: String,
         /// Amount to create
-        amount: u32,
+        amount: AmountArg,
         /// Address to send created tokens (base58 encoded)
         #[arg(long)]
         address: Option<String>,
@@ -273,6 +492,9 @@ enum WalletCommands {
         /// List of data outputs to include in the transaction [use multiple times if needed `-d 123 -d 456`]
         #[arg(short, long)]
         data: Option<Vec<String>>,
+
+        #[command(flatten)]
+        wait: WaitConfirmationArgs,
     },
 
     /// Send a transaction to mint more tokens (requires ownership of a mint authority output)
@@ -280,7 +502,7 @@ enum WalletCommands {
         /// Token UID (hex encoded)
         token: String,
         /// Amount to mint
-        amount: u32,
+        amount: AmountArg,
         /// Address to send new tokens (base58 encoded)
         #[arg(long)]
         address: Option<String>,
@@ -298,6 +520,9 @@ enum WalletCommands {
         /// List of data outputs to include in the transaction
         #[arg(short, long)]
         data: Option<Vec<String>>,
+
+        #[command(flatten)]
+        wait: WaitConfirmationArgs,
     },
 
     /// Send a transaction to melt tokens (requires ownership of a melt authority output)
@@ -305,11 +530,13 @@ enum WalletCommands {
         /// Token UID (hex encoded)
         token: String,
         /// Amount to melt
-        amount: u32,
+        amount: AmountArg,
         #[arg(long)]
         address: Option<String>,
+        /// Send the HTR deposit returned by the melt to this address
         #[arg(long)]
         deposit_address: Option<String>,
+        /// Send the change of the melted token to this address
         #[arg(long)]
         change_address: Option<String>,
         /// Send the melt authority to this address (base58 encoded)
@@ -324,6 +551,9 @@ enum WalletCommands {
         /// List of data outputs to include in the transaction
         #[arg(short, long)]
         data: Option<Vec<String>>,
+
+        #[command(flatten)]
+        wait: WaitConfirmationArgs,
     },
 
     /// Find utxos on the wallet that match the filter
@@ -339,13 +569,13 @@ enum WalletCommands {
         filter_address: Option<String>,
         /// Only utxos with amount smaller than this.
         #[arg(long)]
-        amount_smaller_than: Option<u32>,
+        amount_smaller_than: Option<AmountArg>,
         /// Only utxos with amount greater than this.
         #[arg(long)]
-        amount_bigger_than: Option<u32>,
+        amount_bigger_than: Option<AmountArg>,
         /// The sum of the amounts should not pass this value.
         #[arg(long)]
-        maximum_amount: Option<u32>,
+        maximum_amount: Option<AmountArg>,
         /// Filter by utxos that can be spent right now.
         #[arg(long)]
         only_available_utxos: Option<bool>,
@@ -364,13 +594,16 @@ enum WalletCommands {
         filter_address: Option<String>,
         /// Only utxos with amount smaller than this.
         #[arg(long)]
-        amount_smaller_than: Option<u32>,
+        amount_smaller_than: Option<AmountArg>,
         /// Only utxos with amount greater than this.
         #[arg(long)]
-        amount_bigger_than: Option<u32>,
+        amount_bigger_than: Option<AmountArg>,
         /// The sum of the amounts should not pass this value.
         #[arg(long)]
-        maximum_amount: Option<u32>,
+        maximum_amount: Option<AmountArg>,
+
+        #[command(flatten)]
+        wait: WaitConfirmationArgs,
     },
 
     /// Send a transaction to create a new NFT
@@ -380,7 +613,7 @@ enum WalletCommands {
         /// NFT symbol.
         symbol: String,
         /// Amount to create.
-        amount: u32,
+        amount: u64,
         /// NFT data.
         data: String,
         /// Address to send created tokens (base58 encoded).
@@ -406,6 +639,9 @@ enum WalletCommands {
         /// If we should allow an address not from the wallet as `melt_authority_address`
         #[arg(long)]
         allow_external_melt_authority_address: Option<bool>,
+
+        #[command(flatten)]
+        wait: WaitConfirmationArgs,
     },
 
     /// Stop a wallet
@@ -429,8 +665,8 @@ enum P2shTxProposalCommands {
     SimpleSendTokens {
         /// Destination address (base58 encoded)
         address: String,
-        /// Amount of tokens to send
-        value: u32,
+        /// Amount of tokens to send (e.g. `12.34` or `12.34HTR`)
+        value: AmountArg,
         /// Token UID (hex encoded)
         #[arg(short, long)]
         token: Option<String>,
@@ -443,7 +679,7 @@ enum P2shTxProposalCommands {
     CreateToken {
         name: String,
         symbol: String,
-        amount: u32,
+        amount: AmountArg,
         #[arg(long)]
         address: Option<String>,
         #[arg(long)]
@@ -466,7 +702,7 @@ enum P2shTxProposalCommands {
 
     MintTokens {
         token: String,
-        amount: u32,
+        amount: AmountArg,
         #[arg(long)]
         address: Option<String>,
         #[arg(long)]
@@ -483,7 +719,7 @@ enum P2shTxProposalCommands {
 
     MeltTokens {
         token: String,
-        amount: u32,
+        amount: AmountArg,
         #[arg(long)]
         address: Option<String>,
         #[arg(long)]
@@ -499,8 +735,14 @@ enum P2shTxProposalCommands {
         #[arg(short, long)]
         mark_inputs_as_used: Option<bool>,
     },
-    /// Get this wallet signatures for a tx proposal
-    GetMySignatures { tx_hex: String },
+    /// Get this wallet signatures for a tx proposal, after reviewing its inputs and outputs
+    GetMySignatures {
+        tx_hex: String,
+        /// JSON file with the expected outputs: { outputs: [{ address, value, token? }] },
+        /// any other output must go back to this wallet
+        #[arg(long)]
+        policy: Option<PathBuf>,
+    },
 
     /// Build signatures and sign proposal
     Sign {
@@ -512,20 +754,150 @@ enum P2shTxProposalCommands {
     SignAndPush {
         tx_hex: String,
         signatures: Vec<String>,
+
+        #[command(flatten)]
+        wait: WaitConfirmationArgs,
+    },
+
+    /// Get the signatures of other wallets of this headless and sign (or sign and push) the proposal
+    Collect {
+        tx_hex: String,
+        /// Wallet id of a participant [use multiple times if needed `--signer-wallet w1 --signer-wallet w2`]
+        #[arg(long = "signer-wallet", required = true)]
+        signer_wallets: Vec<String>,
+        /// Sign and push the transaction instead of only signing it
+        #[arg(long)]
+        push: bool,
+        /// JSON file with the expected outputs: { outputs: [{ address, value, token? }] },
+        /// any other output must go back to the wallet
+        #[arg(long)]
+        policy: Option<PathBuf>,
+
+        #[command(flatten)]
+        wait: WaitConfirmationArgs,
     },
+
+    /// Coordinate a tx proposal between the signers with a proposal file
+    Proposal {
+        #[command(subcommand)]
+        command: ProposalCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProposalCommands {
+    /// Create a proposal file from a tx proposal body (see `build`) or an existing tx hex
+    New {
+        /// Proposal file to create
+        file: PathBuf,
+        /// JSON encoded tx proposal body, the same as `build`
+        #[arg(long, required_unless_present = "tx_hex", conflicts_with = "tx_hex")]
+        body: Option<String>,
+        /// Tx proposal already built
+        #[arg(long)]
+        tx_hex: Option<String>,
+        /// Signatures needed to send the transaction (M)
+        #[arg(short = 'm', long)]
+        signatures_required: u32,
+        /// Participants of the multisig wallet (N)
+        #[arg(short = 'n', long)]
+        signers: u32,
+    },
+
+    /// Add signatures to a proposal file, fetching the signatures of this wallet if none are given
+    AddSignature {
+        file: PathBuf,
+        /// Signatures of another signer (the output of `get-my-signatures`)
+        signatures: Option<String>,
+        /// Check the outputs before signing, see `get-my-signatures`
+        #[arg(long, conflicts_with = "signatures")]
+        policy: Option<PathBuf>,
+    },
+
+    /// Show the collected signatures of a proposal file (no request is made)
+    Status { file: PathBuf },
+
+    /// Sign the transaction with the collected signatures once the threshold is reached
+    Finalize {
+        file: PathBuf,
+        /// Also push the signed transaction
+        #[arg(long)]
+        push: bool,
+
+        #[command(flatten)]
+        wait: WaitConfirmationArgs,
+    },
+}
+
+/// Symbol of a custom token to check a typed amount symbol, only asked when there is a symbol
+async fn amount_token_symbol(
+    client: &HeadlessClient,
+    amount: &AmountArg,
+    token: Option<&str>,
+) -> Result<Option<String>, HeadlessCliError> {
+    match token {
+        Some(token) if token != HTR_UID && amount.symbol().is_some() => {
+            Ok(Some(client.token_symbol(token).await?))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Units of an amount of `token` (HTR if not set), a typed symbol must be the one of the token
+async fn token_units(
+    client: &HeadlessClient,
+    amount: &AmountArg,
+    token: Option<&str>,
+) -> Result<u64, HeadlessCliError> {
+    let symbol = amount_token_symbol(client, amount, token).await?;
+    let amount = amount.to_token_amount(client.config().raw_units, token, symbol.as_deref())?;
+    Ok(amount.units())
+}
+
+/// Units of an amount of a token being created, a typed symbol must be the new symbol
+fn new_token_units(
+    client: &HeadlessClient,
+    amount: &AmountArg,
+    symbol: &str,
+) -> Result<u64, HeadlessCliError> {
+    amount.check_symbol(Some(symbol))?;
+    Ok(amount.to_amount(client.config().raw_units)?.units())
+}
+
+/// Convert an optional amount argument of `token` to units
+async fn optional_units(
+    client: &HeadlessClient,
+    amount: &Option<AmountArg>,
+    token: Option<&str>,
+) -> Result<Option<u64>, HeadlessCliError> {
+    match amount {
+        Some(amount) => Ok(Some(token_units(client, amount, token).await?)),
+        None => Ok(None),
+    }
+}
+
+/// Wallet id from the command line or the configured default.
+fn resolve_wallet_id(client: &HeadlessClient, wallet_id: &Option<String>) -> String {
+    wallet_id
+        .clone()
+        .unwrap_or_else(|| client.config().wallet_id.clone())
+}
+
+/// Token from the command line or the configured default.
+fn resolve_token(client: &HeadlessClient, token: &Option<String>) -> Option<String> {
+    token.clone().or_else(|| client.config().token.clone())
 }
 
 async fn handle_custom(
-    config: CliConfig,
+    client: &HeadlessClient,
     custom_cmd: &CustomCommands,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<(), HeadlessCliError> {
     match custom_cmd {
         CustomCommands::ListTokens { wallet_id } => {
             let params = ParamsCustomListTokens {
-                config,
-                wallet_id: wallet_id.to_string(),
+                wallet_id: resolve_wallet_id(client, wallet_id),
             };
-            handle_list_tokens(params).await?;
+            handle_list_tokens(client, params).await?;
         }
 
         CustomCommands::Curl {
@@ -535,13 +907,12 @@ async fn handle_custom(
             path,
         } => {
             let params = ParamsCustomCurl {
-                config,
-                wallet_id: wallet_id.to_string(),
+                wallet_id: resolve_wallet_id(client, wallet_id),
                 post: *post,
                 data: *data,
                 path: path.to_string(),
             };
-            handle_custom_curl(params).await?;
+            handle_custom_curl(client, params).await?;
         }
     }
 
@@ -549,17 +920,20 @@ async fn handle_custom(
 }
 
 async fn handle_hsm(
-    config: CliConfig,
+    client: &HeadlessClient,
     hsm_cmd: &HsmCommands,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<(), HeadlessCliError> {
     match hsm_cmd {
-        HsmCommands::Start { wallet_id, hsm_key } => {
+        HsmCommands::Start {
+            wallet_id,
+            hsm_key,
+            wait,
+        } => {
             let params = ParamsHsmStart {
-                config,
-                wallet_id: wallet_id.to_string(),
+                wallet_id: resolve_wallet_id(client, wallet_id),
                 hsm_key: hsm_key.to_string(),
             };
-            handle_hsm_start(params).await?;
+            handle_hsm_start(client, params, wait.timeout()).await?;
         }
     }
 
@@ -567,17 +941,20 @@ async fn handle_hsm(
 }
 
 async fn handle_fireblocks(
-    config: CliConfig,
+    client: &HeadlessClient,
     fb_cmd: &FireblocksCommands,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<(), HeadlessCliError> {
     match fb_cmd {
-        FireblocksCommands::Start { wallet_id, xpub } => {
+        FireblocksCommands::Start {
+            wallet_id,
+            xpub,
+            wait,
+        } => {
             let params = ParamsFireblocksStart {
-                config,
-                wallet_id: wallet_id.to_string(),
+                wallet_id: resolve_wallet_id(client, wallet_id),
                 xpub: xpub.to_string(),
             };
-            handle_fireblocks_start(params).await?;
+            handle_fireblocks_start(client, params, wait.timeout()).await?;
         }
     }
 
@@ -585,19 +962,18 @@ async fn handle_fireblocks(
 }
 
 async fn handle_p2sh_txproposal(
-    config: CliConfig,
+    client: &HeadlessClient,
     wallet_id: String,
     command: &P2shTxProposalCommands,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<(), HeadlessCliError> {
     match command {
         P2shTxProposalCommands::Build { body } => {
             // handle build
             let params = ParamsP2shTxProposalBuild {
-                config,
                 wallet_id,
                 body: body.to_string(),
             };
-            handle_p2sh_txproposal_build(params).await?;
+            handle_p2sh_txproposal_build(client, params).await?;
         }
 
         P2shTxProposalCommands::SimpleSendTokens {
@@ -607,17 +983,17 @@ async fn handle_p2sh_txproposal(
             change_address,
             mark_inputs_as_used,
         } => {
+            let token = resolve_token(client, token);
             let params = ParamsP2shTxProposalBuildSimpleSendTokens {
-                config,
                 wallet_id,
                 address: address.clone(),
-                value: *value,
-                token: token.clone(),
+                value: token_units(client, value, token.as_deref()).await?,
+                token,
                 change_address: change_address.clone(),
                 mark_inputs_as_used: *mark_inputs_as_used,
             };
 
-            handle_p2sh_txproposal_build_simple_send_tokens(params).await?;
+            handle_p2sh_txproposal_build_simple_send_tokens(client, params).await?;
         }
 
         P2shTxProposalCommands::CreateToken {
@@ -635,11 +1011,10 @@ async fn handle_p2sh_txproposal(
             mark_inputs_as_used,
         } => {
             let params = ParamsWalletP2shTxProposalCreateToken {
-                config,
                 wallet_id,
                 name: name.to_string(),
                 symbol: symbol.to_string(),
-                amount: *amount,
+                amount: new_token_units(client, amount, symbol)?,
                 address: address.clone(),
                 change_address: change_address.clone(),
                 create_mint: *create_mint,
@@ -650,7 +1025,7 @@ async fn handle_p2sh_txproposal(
                 allow_external_melt_authority_address: *allow_external_melt_authority_address,
                 mark_inputs_as_used: *mark_inputs_as_used,
             };
-            handle_p2sh_txproposal_create_token(params).await?;
+            handle_p2sh_txproposal_create_token(client, params).await?;
         }
 
         P2shTxProposalCommands::MintTokens {
@@ -664,10 +1039,9 @@ async fn handle_p2sh_txproposal(
             mark_inputs_as_used,
         } => {
             let params = ParamsWalletP2shTxProposalMintTokens {
-                config,
                 wallet_id,
                 token: token.to_string(),
-                amount: *amount,
+                amount: token_units(client, amount, Some(token)).await?,
                 address: address.clone(),
                 change_address: change_address.clone(),
                 create_mint: *create_mint,
@@ -675,7 +1049,7 @@ async fn handle_p2sh_txproposal(
                 allow_external_mint_authority_address: *allow_external_mint_authority_address,
                 mark_inputs_as_used: *mark_inputs_as_used,
             };
-            handle_p2sh_txproposal_mint_tokens(params).await?;
+            handle_p2sh_txproposal_mint_tokens(client, params).await?;
         }
 
         P2shTxProposalCommands::MeltTokens {
@@ -690,10 +1064,9 @@ async fn handle_p2sh_txproposal(
             mark_inputs_as_used,
         } => {
             let params = ParamsWalletP2shTxProposalMeltTokens {
-                config,
                 wallet_id,
                 token: token.to_string(),
-                amount: *amount,
+                amount: token_units(client, amount, Some(token)).await?,
                 address: address.clone(),
                 deposit_address: deposit_address.clone(),
                 change_address: change_address.clone(),
@@ -702,36 +1075,110 @@ async fn handle_p2sh_txproposal(
                 allow_external_melt_authority_address: *allow_external_melt_authority_address,
                 mark_inputs_as_used: *mark_inputs_as_used,
             };
-            handle_p2sh_txproposal_melt_tokens(params).await?;
+            handle_p2sh_txproposal_melt_tokens(client, params).await?;
         }
 
-        P2shTxProposalCommands::GetMySignatures { tx_hex } => {
+        P2shTxProposalCommands::GetMySignatures { tx_hex, policy } => {
             let params = ParamsP2shTxProposalGetMySignatures {
-                config,
                 wallet_id,
                 tx_hex: tx_hex.clone(),
             };
-            handle_p2sh_txproposal_get_my_signatures(params).await?;
+            handle_p2sh_txproposal_get_my_signatures(client, params, policy.as_deref()).await?;
         }
 
         P2shTxProposalCommands::Sign { tx_hex, signatures } => {
             let params = ParamsP2shTxProposalSign {
-                config,
                 wallet_id,
                 tx_hex: tx_hex.clone(),
                 signatures: signatures.clone(),
             };
-            handle_p2sh_txproposal_sign(params).await?;
+            handle_p2sh_txproposal_sign(client, params).await?;
         }
 
-        P2shTxProposalCommands::SignAndPush { tx_hex, signatures } => {
+        P2shTxProposalCommands::SignAndPush {
+            tx_hex,
+            signatures,
+            wait,
+        } => {
             let params = ParamsP2shTxProposalSign {
-                config,
                 wallet_id,
                 tx_hex: tx_hex.clone(),
                 signatures: signatures.clone(),
             };
-            handle_p2sh_txproposal_sign_and_push(params).await?;
+            handle_p2sh_txproposal_sign_and_push(client, params, wait.params()).await?;
+        }
+
+        P2shTxProposalCommands::Collect {
+            tx_hex,
+            signer_wallets,
+            push,
+            policy,
+            wait,
+        } => {
+            let params = ParamsP2shTxProposalCollect {
+                wallet_id,
+                tx_hex: tx_hex.clone(),
+                signer_wallets: signer_wallets.clone(),
+                push: *push,
+            };
+            handle_p2sh_txproposal_collect(client, params, policy.as_deref(), wait.params())
+                .await?;
+        }
+
+        P2shTxProposalCommands::Proposal { command } => {
+            handle_p2sh_proposal(client, wallet_id, command).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_p2sh_proposal(
+    client: &HeadlessClient,
+    wallet_id: String,
+    command: &ProposalCommands,
+) -> Result<(), HeadlessCliError> {
+    match command {
+        ProposalCommands::New {
+            file,
+            body,
+            tx_hex,
+            signatures_required,
+            signers,
+        } => {
+            let params = ParamsP2shProposalNew {
+                wallet_id,
+                file: file.clone(),
+                body: body.clone(),
+                tx_hex: tx_hex.clone(),
+                signatures_required: *signatures_required,
+                signers: *signers,
+            };
+            handle_p2sh_proposal_new(client, params).await?;
+        }
+
+        ProposalCommands::AddSignature {
+            file,
+            signatures,
+            policy,
+        } => {
+            let params = ParamsP2shProposalAddSignature {
+                wallet_id,
+                file: file.clone(),
+                signatures: signatures.clone(),
+            };
+            handle_p2sh_proposal_add_signature(client, params, policy.as_deref()).await?;
+        }
+
+        ProposalCommands::Status { file } => handle_p2sh_proposal_status(client, file)?,
+
+        ProposalCommands::Finalize { file, push, wait } => {
+            let params = ParamsP2shProposalFinalize {
+                wallet_id,
+                file: file.clone(),
+                push: *push,
+            };
+            handle_p2sh_proposal_finalize(client, params, wait.params()).await?;
         }
     }
 
@@ -739,22 +1186,28 @@ async fn handle_p2sh_txproposal(
 }
 
 async fn handle_wallet(
-    config: CliConfig,
+    client: &HeadlessClient,
     wallet_id: String,
     wallet_cmd: &WalletCommands,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<(), HeadlessCliError> {
+    let raw_units = client.config().raw_units;
+
     match wallet_cmd {
         WalletCommands::Status {} => {
-            handle_status(config, wallet_id.to_string()).await?;
+            handle_status(client, wallet_id.to_string()).await?;
+        }
+
+        WalletCommands::WaitReady { wait_timeout } => {
+            let timeout = Duration::from_secs(*wait_timeout);
+            handle_wait_ready(client, wallet_id.to_string(), timeout).await?;
         }
 
         WalletCommands::Balance { token } => {
             let params = ParamsWalletBalance {
-                config,
                 wallet_id,
-                token: token.clone(),
+                token: resolve_token(client, token),
             };
-            handle_balance(params).await?;
+            handle_balance(client, params).await?;
         }
 
         WalletCommands::Address {
@@ -762,73 +1215,76 @@ async fn handle_wallet(
             mark_as_used,
         } => {
             let params = ParamsWalletAddress {
-                config,
                 wallet_id,
                 index: *index,
                 mark_as_used: *mark_as_used,
             };
-            handle_address(params).await?;
+            handle_address(client, params).await?;
         }
 
         WalletCommands::AddressInfo { address, token } => {
             let params = ParamsWalletAddressInfo {
-                config,
                 wallet_id,
                 address: address.to_string(),
-                token: token.clone(),
+                token: resolve_token(client, token),
             };
-            handle_address_info(params).await?;
+            handle_address_info(client, params).await?;
         }
 
         WalletCommands::AddressIndex { address } => {
             let params = ParamsWalletAddressIndex {
-                config,
                 wallet_id,
                 address: address.to_string(),
             };
-            handle_address_index(params).await?;
+            handle_address_index(client, params).await?;
         }
 
         WalletCommands::Addresses {} => {
-            let params = ParamsWalletAddresses { config, wallet_id };
-            handle_addresses(params).await?;
+            let params = ParamsWalletAddresses { wallet_id };
+            handle_addresses(client, params).await?;
         }
 
         WalletCommands::TxHistory { limit } => {
             let params = ParamsWalletTxHistory {
-                config,
                 wallet_id,
                 limit: *limit,
             };
-            handle_tx_history(params).await?;
+            handle_tx_history(client, params).await?;
         }
 
         WalletCommands::Transaction { id } => {
             let params = ParamsWalletTransaction {
-                config,
                 wallet_id,
                 id: id.to_string(),
             };
-            handle_transaction(params).await?;
+            handle_transaction(client, params).await?;
         }
 
-        WalletCommands::Decode { tx_hex, partial_tx } => {
+        WalletCommands::Decode {
+            tx_hex: Some(tx_hex),
+            offline: true,
+            ..
+        } => {
+            handle_decode_offline(client, tx_hex)?;
+        }
+
+        WalletCommands::Decode {
+            tx_hex, partial_tx, ..
+        } => {
             let params = ParamsWalletDecode {
-                config,
                 wallet_id,
                 tx_hex: tx_hex.clone(),
                 partial_tx: partial_tx.clone(),
             };
-            handle_decode(params).await?;
+            handle_decode(client, params).await?;
         }
 
         WalletCommands::TxConfirmation { id } => {
             let params = ParamsWalletTxConfirmation {
-                config,
                 wallet_id,
                 id: id.to_string(),
             };
-            handle_tx_confirmation(params).await?;
+            handle_tx_confirmation(client, params).await?;
         }
 
         WalletCommands::SimpleSend {
@@ -836,25 +1292,66 @@ async fn handle_wallet(
             value,
             change_address,
             token,
+            wait,
         } => {
+            let token = resolve_token(client, token);
             let params = ParamsWalletSimpleSend {
-                config,
                 wallet_id,
                 address: address.to_string(),
-                value: *value,
+                value: token_units(client, value, token.as_deref()).await?,
                 change_address: change_address.clone(),
-                token: token.clone(),
+                token,
             };
-            handle_simple_send(params).await?;
+            handle_simple_send(client, params, wait.params()).await?;
         }
 
-        WalletCommands::Send { body } => {
-            let params = ParamsWalletSend {
-                config,
+        WalletCommands::Send {
+            outputs,
+            data,
+            inputs,
+            change_address,
+            body,
+            wait,
+        } => {
+            let body = match body {
+                Some(body) => body.to_string(),
+                None => {
+                    // Outputs without a token use the profile token, like simple-send
+                    let default_token = resolve_token(client, &None);
+                    let mut send_outputs = vec![];
+                    for output in outputs.iter() {
+                        let token = output.token.as_deref().or(default_token.as_deref());
+                        let symbol = amount_token_symbol(client, &output.amount, token).await?;
+                        send_outputs.push(output.to_output(
+                            raw_units,
+                            default_token.as_deref(),
+                            symbol.as_deref(),
+                        )?);
+                    }
+                    send_outputs.extend(data.iter().cloned().map(SendOutput::Data));
+                    build_send_tx_body(&send_outputs, inputs, change_address.as_deref())?
+                }
+            };
+            let params = ParamsWalletSend { wallet_id, body };
+            handle_send(client, params, wait.params()).await?;
+        }
+
+        WalletCommands::BatchSend {
+            file,
+            results,
+            max_outputs,
+            change_address,
+            retry_unknown,
+        } => {
+            let params = ParamsWalletBatchSend {
                 wallet_id,
-                body: body.to_string(),
+                file: file.clone(),
+                results: results.clone(),
+                max_outputs: *max_outputs,
+                change_address: change_address.clone(),
+                retry_unknown: *retry_unknown,
             };
-            handle_send(params).await?;
+            handle_batch_send(client, params).await?;
         }
 
         WalletCommands::CreateToken {
@@ -870,13 +1367,13 @@ async fn handle_wallet(
             melt_authority_address,
             allow_external_melt_authority_address,
             data,
+            wait,
         } => {
             let params = ParamsWalletCreateToken {
-                config,
                 wallet_id,
                 name: name.to_string(),
                 symbol: symbol.to_string(),
-                amount: *amount,
+                amount: new_token_units(client, amount, symbol)?,
                 address: address.clone(),
                 change_address: change_address.clone(),
                 create_mint: *create_mint,
@@ -887,7 +1384,7 @@ async fn handle_wallet(
                 allow_external_melt_authority_address: *allow_external_melt_authority_address,
                 data: data.clone(),
             };
-            handle_create_token(params).await?;
+            handle_create_token(client, params, wait.params()).await?;
         }
 
         WalletCommands::MintTokens {
@@ -899,12 +1396,12 @@ async fn handle_wallet(
             allow_external_mint_authority_address,
             unshift_data,
             data,
+            wait,
         } => {
             let params = ParamsWalletMintTokens {
-                config,
                 wallet_id,
                 token: token.to_string(),
-                amount: *amount,
+                amount: token_units(client, amount, Some(token)).await?,
                 address: address.clone(),
                 change_address: change_address.clone(),
                 mint_authority_address: mint_authority_address.clone(),
@@ -912,7 +1409,7 @@ async fn handle_wallet(
                 unshift_data: *unshift_data,
                 data: data.clone(),
             };
-            handle_mint_tokens(params).await?;
+            handle_mint_tokens(client, params, wait.params()).await?;
         }
 
         WalletCommands::MeltTokens {
@@ -925,12 +1422,12 @@ async fn handle_wallet(
             allow_external_melt_authority_address,
             unshift_data,
             data,
+            wait,
         } => {
             let params = ParamsWalletMeltTokens {
-                config,
                 wallet_id,
                 token: token.to_string(),
-                amount: *amount,
+                amount: token_units(client, amount, Some(token)).await?,
                 address: address.clone(),
                 deposit_address: deposit_address.clone(),
                 change_address: change_address.clone(),
@@ -939,7 +1436,7 @@ async fn handle_wallet(
                 unshift_data: *unshift_data,
                 data: data.clone(),
             };
-            handle_melt_tokens(params).await?;
+            handle_melt_tokens(client, params, wait.params()).await?;
         }
 
         WalletCommands::UtxoFilter {
@@ -951,18 +1448,20 @@ async fn handle_wallet(
             maximum_amount,
             only_available_utxos,
         } => {
+            let token = resolve_token(client, token);
             let params = ParamsWalletUtxoFilter {
-                config,
                 wallet_id,
                 max_utxos: *max_utxos,
                 token: token.clone(),
                 filter_address: filter_address.clone(),
-                amount_smaller_than: *amount_smaller_than,
-                amount_bigger_than: *amount_bigger_than,
-                maximum_amount: *maximum_amount,
+                amount_smaller_than: optional_units(client, amount_smaller_than, token.as_deref())
+                    .await?,
+                amount_bigger_than: optional_units(client, amount_bigger_than, token.as_deref())
+                    .await?,
+                maximum_amount: optional_units(client, maximum_amount, token.as_deref()).await?,
                 only_available_utxos: *only_available_utxos,
             };
-            handle_utxo_filter(params).await?;
+            handle_utxo_filter(client, params).await?;
         }
 
         WalletCommands::UtxoConsolidation {
@@ -972,18 +1471,21 @@ async fn handle_wallet(
             amount_smaller_than,
             amount_bigger_than,
             maximum_amount,
+            wait,
         } => {
+            let token = resolve_token(client, token);
             let params = ParamsWalletUtxoConsolidation {
-                config,
                 wallet_id,
                 max_utxos: *max_utxos,
                 token: token.clone(),
                 filter_address: filter_address.clone(),
-                amount_bigger_than: *amount_bigger_than,
-                amount_smaller_than: *amount_smaller_than,
-                maximum_amount: *maximum_amount,
+                amount_bigger_than: optional_units(client, amount_bigger_than, token.as_deref())
+                    .await?,
+                amount_smaller_than: optional_units(client, amount_smaller_than, token.as_deref())
+                    .await?,
+                maximum_amount: optional_units(client, maximum_amount, token.as_deref()).await?,
             };
-            handle_utxo_consolidation(params).await?;
+            handle_utxo_consolidation(client, params, wait.params()).await?;
         }
 
         WalletCommands::CreateNft {
@@ -999,9 +1501,9 @@ async fn handle_wallet(
             create_melt,
             melt_authority_address,
             allow_external_melt_authority_address,
+            wait,
         } => {
             let params = ParamsWalletCreateNft {
-                config,
                 wallet_id,
                 name: name.to_string(),
                 symbol: symbol.to_string(),
@@ -1016,16 +1518,16 @@ async fn handle_wallet(
                 melt_authority_address: melt_authority_address.clone(),
                 allow_external_melt_authority_address: *allow_external_melt_authority_address,
             };
-            handle_create_nft(params).await?;
+            handle_create_nft(client, params, wait.params()).await?;
         }
 
         WalletCommands::Stop {} => {
-            let params = ParamsWalletStop { config, wallet_id };
-            handle_stop(params).await?;
+            let params = ParamsWalletStop { wallet_id };
+            handle_stop(client, params).await?;
         }
 
         WalletCommands::P2sh { command } => {
-            handle_p2sh_txproposal(config, wallet_id, command).await?;
+            handle_p2sh_txproposal(client, wallet_id, command).await?;
         }
     }
 
@@ -1035,21 +1537,116 @@ async fn handle_wallet(
 /////////////////////////////////////////// Main
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let cli = Cli::parse();
+async fn main() {
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
 
-    let config = CliConfig {
-        host: cli.host,
-        debug: cli.debug,
-    };
-
-    // Configure logging using the default RUST_LOG envvar
+    // Configure logging using the default RUST_LOG envvar, warnings (e.g. retries) are shown by default
     if cli.debug && env::var("RUST_LOG").is_err() {
         env::set_var("RUST_LOG", "trace");
     }
-    env_logger::init();
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
+    let result = async {
+        let config = load_config(&cli)?;
+        validate_addresses(&matches, config.network)?;
+        run(config, &cli.command).await
+    }
+    .await;
+
+    if let Err(err) = result {
+        if let (true, Some(body)) = (cli.allow_failure, err.response_body()) {
+            println!("{}", body);
+            return;
+        }
 
-    let result: Result<(), Box<dyn std::error::Error>> = match &cli.command {
+        eprintln!("{}", err);
+        process::exit(err.exit_code());
+    }
+}
+
+/// Check every address argument (`*_address` and the `--to` outputs) offline,
+/// so typos and addresses of other networks are rejected before any request.
+fn validate_addresses(
+    matches: &ArgMatches,
+    network: Option<Network>,
+) -> Result<(), HeadlessCliError> {
+    for id in matches.ids().map(|id| id.as_str()) {
+        // Flags like `--allow-external-*-address` are not strings and are skipped
+        if let (true, Ok(Some(addresses))) =
+            (id.ends_with("address"), matches.try_get_many::<String>(id))
+        {
+            for address in addresses {
+                validate_address_network(address, network)?;
+            }
+        }
+        if let Ok(Some(outputs)) = matches.try_get_many::<OutputSpec>(id) {
+            for output in outputs {
+                validate_address_network(&output.address, network)?;
+            }
+        }
+    }
+
+    match matches.subcommand() {
+        Some((_, matches)) => validate_addresses(matches, network),
+        None => Ok(()),
+    }
+}
+
+/// Build the config from the arguments, environment, profile and defaults (in this order).
+fn load_config(cli: &Cli) -> Result<CliConfig, HeadlessCliError> {
+    let config_file = match &cli.config {
+        Some(path) => ConfigFile::load(path)?,
+        None => ConfigFile::load_default()?,
+    };
+    let profile = config_file
+        .profile(cli.profile.as_deref())?
+        .merge(Profile::from_env()?);
+
+    let mut config = profile.apply(CliConfig::default());
+    if let Some(host) = &cli.host {
+        config.host = host.clone();
+    }
+    if let Some(ca_cert) = &cli.ca_cert {
+        config.ca_cert = Some(ca_cert.clone());
+    }
+    if let (Some(client_cert), Some(client_key)) = (&cli.client_cert, &cli.client_key) {
+        config.client_cert = Some(client_cert.clone());
+        config.client_key = Some(client_key.clone());
+    }
+    if let Some(timeout) = cli.timeout {
+        config.timeout = (timeout > 0).then(|| Duration::from_secs(timeout));
+    }
+    if let Some(retries) = cli.retries {
+        config.retries = retries;
+    }
+    config.retry_mutating |= cli.retry_mutating;
+    config.insecure |= cli.insecure;
+    if config.insecure {
+        eprintln!("WARNING: TLS certificate verification is disabled, the connection to the headless is not secure!");
+    }
+    config.debug = cli.debug;
+    config.raw_units = cli.raw_units;
+    if let Some(network) = cli.network {
+        config.network = Some(network);
+    }
+    config.dry_run = cli.dry_run;
+    config.yes = cli.yes;
+    config.check_balance = cli.check_balance;
+    config.query = cli.query.clone();
+    config.output = cli.output.unwrap_or(if io::stdout().is_terminal() {
+        OutputFormat::Table
+    } else {
+        OutputFormat::Json
+    });
+
+    Ok(config)
+}
+
+async fn run(config: CliConfig, command: &Option<Commands>) -> Result<(), HeadlessCliError> {
+    let client = HeadlessClient::new(config)?;
+
+    let result = match command {
         Some(Commands::Start {
             wallet_id,
             seed_key,
@@ -1061,10 +1658,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             history_sync_mode,
             multisig,
             multisig_key,
+            wait,
         }) => {
             let params = ParamsStart {
-                config,
-                wallet_id: wallet_id.to_string(),
+                wallet_id: resolve_wallet_id(&client, wallet_id),
                 seed_key: seed_key.to_string(),
                 passphrase: passphrase.clone(),
                 scan_policy: scan_policy.clone(),
@@ -1075,44 +1672,44 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 multisig: *multisig,
                 multisig_key: multisig_key.clone(),
             };
-            handle_start(params).await
+            handle_start(&client, params, wait.timeout()).await
         }
         Some(Commands::MultisigPubkey {
             seed_key,
             passphrase,
         }) => {
             let params = ParamsMultisigPubkey {
-                config,
                 seed_key: seed_key.to_string(),
                 passphrase: passphrase.clone(),
             };
-            handle_multisig_pubkey(params).await
+            handle_multisig_pubkey(&client, params).await
         }
         Some(Commands::ConfigurationString { token }) => {
             let params = ParamsConfigString {
-                config,
                 token: token.to_string(),
             };
-            handle_configuration_string(params).await
+            handle_configuration_string(&client, params).await
         }
+        Some(Commands::ValidateAddress { address }) => handle_validate_address(&client, address),
+        Some(Commands::Tx {
+            command: TxCommands::Hash { tx_hex, expect },
+        }) => handle_tx_hash(&client, tx_hex, expect.as_deref()),
         Some(Commands::Wallet { wallet_id, command }) => {
-            handle_wallet(config, wallet_id.to_string(), command).await
+            handle_wallet(&client, resolve_wallet_id(&client, wallet_id), command).await
         }
 
-        Some(Commands::Hsm { command }) => handle_hsm(config, command).await,
+        Some(Commands::Hsm { command }) => handle_hsm(&client, command).await,
 
-        Some(Commands::Fireblocks { command }) => handle_fireblocks(config, command).await,
+        Some(Commands::Fireblocks { command }) => handle_fireblocks(&client, command).await,
 
-        Some(Commands::Custom { command }) => handle_custom(config, command).await,
+        Some(Commands::Custom { command }) => handle_custom(&client, command).await,
 
-        None => {
-            return Ok(());
-        }
+        None => Ok(()),
     };
 
-    if let Err(err) = result {
-        println!("{}", err);
+    // A dry run stops at the first request that would change the wallet
+    match result {
+        Err(HeadlessCliError::DryRun(request)) => handle_dry_run(&client, &request),
+        result => result,
     }
-
-    Ok(())
 }
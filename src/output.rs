@@ -0,0 +1,338 @@
+use crate::amount::Amount;
+use crate::batch::BatchSummary;
+use crate::data::*;
+use crate::error::HeadlessCliError;
+use crate::proposal::ProposalStatus;
+use crate::query::query;
+use crate::tx::{DecodedTransaction, TxHashes};
+use crate::validation::Address;
+
+use std::collections::HashSet;
+
+use serde::Serialize;
+use serde_json::Value;
+
+/////////////////////////////////////////// Output formats
+
+/// How command results are printed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Compact json, one line per response
+    Json,
+    /// Indented json
+    Pretty,
+    /// Aligned columns for humans
+    Table,
+    /// Comma separated values with a header line
+    Csv,
+}
+
+/// Rows and columns of a response.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Table {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: &[&str]) -> Self {
+        Table {
+            headers: headers.iter().map(|header| header.to_string()).collect(),
+            rows: vec![],
+        }
+    }
+
+    /// A `key | value` table of every scalar of a json value, nested keys are
+    /// joined with dots (e.g. `serverInfo.version`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use headless_cli::output::Table;
+    /// use serde_json::json;
+    ///
+    /// let table = Table::from_json(&json!({ "success": true, "tx": { "inputs": ["a", "b"] } }));
+    /// assert_eq!(table.rows, vec![
+    ///     vec!["success".to_string(), "true".to_string()],
+    ///     vec!["tx.inputs.0".to_string(), "a".to_string()],
+    ///     vec!["tx.inputs.1".to_string(), "b".to_string()],
+    /// ]);
+    /// ```
+    pub fn from_json(value: &Value) -> Self {
+        let mut table = Table::new(&["key", "value"]);
+        flatten_json("", value, &mut table.rows);
+        table
+    }
+
+    /// Render the table with aligned columns.
+    pub fn render_table(&self) -> String {
+        let mut widths: Vec<usize> = self.headers.iter().map(|header| header.len()).collect();
+        for row in self.rows.iter() {
+            for (i, cell) in row.iter().enumerate() {
+                widths[i] = widths[i].max(cell.chars().count());
+            }
+        }
+
+        let render_row = |row: &Vec<String>| {
+            row.iter()
+                .enumerate()
+                .map(|(i, cell)| format!("{:width$}", cell, width = widths[i]))
+                .collect::<Vec<String>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        };
+
+        let mut lines = vec![render_row(&self.headers)];
+        lines.extend(self.rows.iter().map(render_row));
+        lines.join("\n")
+    }
+
+    /// Render the table as csv, quoting cells when needed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use headless_cli::output::Table;
+    ///
+    /// let mut table = Table::new(&["name", "symbol"]);
+    /// table.rows.push(vec!["My, token".to_string(), "MTK".to_string()]);
+    /// assert_eq!(table.render_csv(), "name,symbol\n\"My, token\",MTK");
+    /// ```
+    pub fn render_csv(&self) -> String {
+        let render_row = |row: &Vec<String>| {
+            row.iter()
+                .map(|cell| csv_cell(cell))
+                .collect::<Vec<String>>()
+                .join(",")
+        };
+
+        let mut lines = vec![render_row(&self.headers)];
+        lines.extend(self.rows.iter().map(render_row));
+        lines.join("\n")
+    }
+}
+
+fn flatten_json(prefix: &str, value: &Value, rows: &mut Vec<Vec<String>>) {
+    let join = |key: &str| {
+        if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", prefix, key)
+        }
+    };
+
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter() {
+                flatten_json(&join(key), value, rows);
+            }
+        }
+        Value::Array(list) => {
+            for (i, value) in list.iter().enumerate() {
+                flatten_json(&join(&i.to_string()), value, rows);
+            }
+        }
+        _ => rows.push(vec![prefix.to_string(), scalar_to_string(value)]),
+    }
+}
+
+/// A json scalar without the quotes of strings, `null` is an empty string.
+pub fn scalar_to_string(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn csv_cell(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+/// Responses that can be printed as a table.
+///
+/// The default is a `key | value` table of the json response, responses with
+/// lists (balance, addresses, history, utxos) have their own columns.
+pub trait Tabular: Serialize {
+    fn table(&self) -> Result<Table, HeadlessCliError> {
+        Ok(Table::from_json(&serde_json::to_value(self)?))
+    }
+}
+
+/// Format the response as requested.
+pub fn format_output<T: Tabular>(
+    response: &T,
+    format: OutputFormat,
+) -> Result<String, HeadlessCliError> {
+    Ok(match format {
+        OutputFormat::Json => serde_json::to_string(response)?,
+        OutputFormat::Pretty => serde_json::to_string_pretty(response)?,
+        OutputFormat::Table => response.table()?.render_table(),
+        OutputFormat::Csv => response.table()?.render_csv(),
+    })
+}
+
+/// Format the field selected by `path` (see [`query`]).
+///
+/// Scalars are printed bare and lists of scalars one per line, so they can be
+/// used directly in shell scripts.
+///
+/// # Examples
+///
+/// ```
+/// use headless_cli::data::BalanceResponse;
+/// use headless_cli::output::{format_query, OutputFormat};
+///
+/// let balance = BalanceResponse { available: 1234, locked: 0, extra: Default::default() };
+/// assert_eq!(format_query(&balance, "available", OutputFormat::Table).unwrap(), "1234");
+/// ```
+pub fn format_query<T: Serialize>(
+    response: &T,
+    path: &str,
+    format: OutputFormat,
+) -> Result<String, HeadlessCliError> {
+    let value = query(&serde_json::to_value(response)?, path)?;
+
+    match &value {
+        Value::Object(_) => format_output(&value, format),
+        Value::Array(list) if list.iter().any(|item| item.is_object() || item.is_array()) => {
+            format_output(&value, format)
+        }
+        Value::Array(list) => Ok(list
+            .iter()
+            .map(scalar_to_string)
+            .collect::<Vec<String>>()
+            .join("\n")),
+        scalar => Ok(scalar_to_string(scalar)),
+    }
+}
+
+impl Tabular for SuccessResponse {}
+impl Tabular for MultisigPubkeyResponse {}
+impl Tabular for ConfigurationStringResponse {}
+impl Tabular for StatusResponse {}
+impl Tabular for AddressResponse {}
+impl Tabular for AddressInfoResponse {}
+impl Tabular for AddressIndexResponse {}
+impl Tabular for DecodeResponse {}
+impl Tabular for TxConfirmationResponse {}
+impl Tabular for SendTxResponse {}
+impl Tabular for UtxoConsolidationResponse {}
+impl Tabular for TxProposalResponse {}
+impl Tabular for GetMySignaturesResponse {}
+impl Tabular for Value {}
+impl Tabular for BatchSummary {}
+impl Tabular for DryRunRequest {}
+impl Tabular for Address {}
+impl Tabular for DecodedTransaction {}
+impl Tabular for TxHashes {}
+impl Tabular for ProposalStatus {}
+
+impl Tabular for BalanceResponse {
+    fn table(&self) -> Result<Table, HeadlessCliError> {
+        let mut table = Table::new(&["available", "locked"]);
+        table.rows.push(vec![
+            Amount::from_units(self.available).to_string(),
+            Amount::from_units(self.locked).to_string(),
+        ]);
+        Ok(table)
+    }
+}
+
+impl Tabular for AddressesResponse {
+    fn table(&self) -> Result<Table, HeadlessCliError> {
+        let mut table = Table::new(&["index", "address"]);
+        for (index, address) in self.addresses.iter().enumerate() {
+            table.rows.push(vec![index.to_string(), address.clone()]);
+        }
+        Ok(table)
+    }
+}
+
+const HISTORY_HEADERS: [&str; 7] = [
+    "tx_id",
+    "timestamp",
+    "voided",
+    "direction",
+    "value",
+    "token",
+    "address",
+];
+
+impl HistoryTx {
+    /// One row per input and output of the transaction.
+    fn history_rows(&self) -> Vec<Vec<String>> {
+        let row = |direction: &str, value: u64, token: &str, decoded: &DecodedOutput| {
+            vec![
+                self.tx_id.clone(),
+                self.timestamp.to_string(),
+                self.is_voided.to_string(),
+                direction.to_string(),
+                Amount::from_units(value).to_string(),
+                token.to_string(),
+                decoded.address.clone().unwrap_or_default(),
+            ]
+        };
+
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|input| row("in", input.value, &input.token, &input.decoded));
+        let outputs = self
+            .outputs
+            .iter()
+            .map(|output| row("out", output.value, &output.token, &output.decoded));
+        inputs.chain(outputs).collect()
+    }
+}
+
+impl Tabular for HistoryTx {
+    fn table(&self) -> Result<Table, HeadlessCliError> {
+        let mut table = Table::new(&HISTORY_HEADERS);
+        table.rows = self.history_rows();
+        Ok(table)
+    }
+}
+
+impl Tabular for Vec<HistoryTx> {
+    fn table(&self) -> Result<Table, HeadlessCliError> {
+        let mut table = Table::new(&HISTORY_HEADERS);
+        table.rows = self.iter().flat_map(HistoryTx::history_rows).collect();
+        Ok(table)
+    }
+}
+
+impl Tabular for UtxoFilterResponse {
+    fn table(&self) -> Result<Table, HeadlessCliError> {
+        let mut table = Table::new(&["tx_id", "index", "address", "amount", "locked"]);
+        for utxo in self.utxos.iter() {
+            table.rows.push(vec![
+                utxo.tx_id.clone(),
+                utxo.index.to_string(),
+                utxo.address.clone(),
+                Amount::from_units(utxo.amount).to_string(),
+                utxo.locked.to_string(),
+            ]);
+        }
+        Ok(table)
+    }
+}
+
+impl Tabular for HashSet<String> {
+    fn table(&self) -> Result<Table, HeadlessCliError> {
+        let mut tokens: Vec<&String> = self.iter().collect();
+        tokens.sort();
+
+        let mut table = Table::new(&["token"]);
+        table.rows = tokens
+            .into_iter()
+            .map(|token| vec![token.clone()])
+            .collect();
+        Ok(table)
+    }
+}
@@ -1,3 +1,12 @@
+use crate::config::Auth;
+use crate::error::HeadlessCliError;
+use crate::output::OutputFormat;
+use crate::validation::Network;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
 /////////////////////////////////////////// handlers params
 
 /// The common configuration of all commands in the cli
@@ -7,12 +16,95 @@ pub struct CliConfig {
     pub host: String,
     /// Enable reqwest trace logging
     pub debug: bool,
+    /// Amounts typed by the user are in units instead of decimal values
+    pub raw_units: bool,
+    /// How responses are printed
+    pub output: OutputFormat,
+    /// Only print this field of the responses (see [`crate::query::query`])
+    pub query: Option<String>,
+    /// Wallet id used when a command does not specify one
+    pub wallet_id: String,
+    /// Reject addresses of other networks before sending any request
+    pub network: Option<Network>,
+    /// Token used when a command does not specify one, headless defaults to HTR
+    pub token: Option<String>,
+    /// Timeout to connect to the headless
+    pub connect_timeout: Duration,
+    /// Timeout for the whole request, no timeout if not set. A request that
+    /// changes the wallet may still be executed after timing out
+    pub timeout: Option<Duration>,
+    /// How many times idempotent requests are retried on connection errors
+    /// and 502, 503 or 504 responses
+    pub retries: u32,
+    /// Delay before the first retry, doubled on each retry
+    pub retry_backoff: Duration,
+    /// Also retry requests that change the wallet, which may execute them twice
+    pub retry_mutating: bool,
+    /// Extra headers sent on every request
+    pub headers: HashMap<String, String>,
+    /// Credentials sent on every request
+    pub auth: Auth,
+    /// PEM file with extra CA certificates to trust
+    pub ca_cert: Option<PathBuf>,
+    /// PEM file with the client certificate for mTLS
+    pub client_cert: Option<PathBuf>,
+    /// PEM file with the PKCS#8 key of the client certificate
+    pub client_key: Option<PathBuf>,
+    /// Accept invalid and self-signed certificates, for testing only
+    pub insecure: bool,
+    /// Do not send requests that change the wallet, return them as [`HeadlessCliError::DryRun`]
+    pub dry_run: bool,
+    /// Do not ask for confirmation before moving funds
+    pub yes: bool,
+    /// Check the balance before sending, see [`crate::client::HeadlessClient::check_funds`]
+    pub check_balance: bool,
+}
+
+impl Default for CliConfig {
+    fn default() -> Self {
+        CliConfig {
+            host: "http://localhost:8000".to_string(),
+            debug: false,
+            raw_units: false,
+            output: OutputFormat::Json,
+            query: None,
+            wallet_id: "default".to_string(),
+            network: None,
+            token: None,
+            connect_timeout: Duration::from_secs(10),
+            timeout: Some(Duration::from_secs(60)),
+            retries: 3,
+            retry_backoff: Duration::from_millis(500),
+            retry_mutating: false,
+            headers: HashMap::new(),
+            auth: Auth::default(),
+            ca_cert: None,
+            client_cert: None,
+            client_key: None,
+            insecure: false,
+            dry_run: false,
+            yes: false,
+            check_balance: false,
+        }
+    }
+}
+
+impl CliConfig {
+    /// Every header configured to be sent on all requests, extra headers
+    /// first and then the credentials.
+    pub fn request_headers(&self) -> Result<Vec<(String, String)>, HeadlessCliError> {
+        let mut headers: Vec<(String, String)> = self
+            .headers
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        headers.extend(self.auth.headers()?);
+        Ok(headers)
+    }
 }
 
 /// Arguments for the start command
 pub struct ParamsStart {
-    /// Common config
-    pub config: CliConfig,
     /// wallet-id used to name the wallet for subsequent calls
     pub wallet_id: String,
     /// seed-key used to generate the wallet
@@ -38,8 +130,6 @@ pub struct ParamsStart {
 
 /// Arguments for the HSM start command
 pub struct ParamsHsmStart {
-    /// Common config
-    pub config: CliConfig,
     /// wallet-id used to name the wallet for subsequent calls
     pub wallet_id: String,
     /// seed-key used to generate the wallet
@@ -48,8 +138,6 @@ pub struct ParamsHsmStart {
 
 /// Arguments for the Fireblocks start command
 pub struct ParamsFireblocksStart {
-    /// Common config
-    pub config: CliConfig,
     /// wallet-id used to name the wallet for subsequent calls
     pub wallet_id: String,
     /// xpub used to generate the wallet
@@ -58,8 +146,6 @@ pub struct ParamsFireblocksStart {
 
 /// Arguments for the multisig_pubkey command
 pub struct ParamsMultisigPubkey {
-    /// Common config
-    pub config: CliConfig,
     /// Seed key to use when generating the xpubkey
     pub seed_key: String,
     /// Optional passphrase to include
@@ -68,16 +154,12 @@ pub struct ParamsMultisigPubkey {
 
 /// Arguments for the configuration-string command
 pub struct ParamsConfigString {
-    /// Common config
-    pub config: CliConfig,
     /// Token to get the configuration string
     pub token: String,
 }
 
 /// Arguments for the wallet balance command
 pub struct ParamsWalletBalance {
-    /// Common config
-    pub config: CliConfig,
     /// wallet-id used to identify the wallet
     pub wallet_id: String,
     /// Get the balance of this token, headless will default to HTR
@@ -86,8 +168,6 @@ pub struct ParamsWalletBalance {
 
 /// Arguments for the wallet address command
 pub struct ParamsWalletAddress {
-    /// Common config
-    pub config: CliConfig,
     /// wallet-id used to identify the wallet
     pub wallet_id: String,
     /// Optional index, to fetch a specific address
@@ -98,8 +178,6 @@ pub struct ParamsWalletAddress {
 
 /// Arguments for the wallet tx-history command
 pub struct ParamsWalletTxHistory {
-    /// Common config
-    pub config: CliConfig,
     /// wallet-id used to identify the wallet
     pub wallet_id: String,
     /// Optionally limit the number of entries retrieved, will not impact performance
@@ -108,8 +186,6 @@ pub struct ParamsWalletTxHistory {
 
 /// Arguments for the wallet address-info command
 pub struct ParamsWalletAddressInfo {
-    /// Common config
-    pub config: CliConfig,
     /// wallet-id used to identify the wallet
     pub wallet_id: String,
     /// address to get the info for
@@ -120,8 +196,6 @@ pub struct ParamsWalletAddressInfo {
 
 /// Arguments for the wallet address-index command
 pub struct ParamsWalletAddressIndex {
-    /// Common config
-    pub config: CliConfig,
     /// wallet-id used to indentify the wallet
     pub wallet_id: String,
     /// address to find the index of
@@ -130,16 +204,12 @@ pub struct ParamsWalletAddressIndex {
 
 /// Arguments for the wallet addresses command
 pub struct ParamsWalletAddresses {
-    /// Common config
-    pub config: CliConfig,
     /// wallet-id used to indentify the wallet
     pub wallet_id: String,
 }
 
 /// Arguments for the wallet transaction command
 pub struct ParamsWalletTransaction {
-    /// Common config
-    pub config: CliConfig,
     /// wallet-id used to indentify the wallet
     pub wallet_id: String,
     /// Transaction id to find the details for
@@ -148,8 +218,6 @@ pub struct ParamsWalletTransaction {
 
 /// Arguments for the wallet decode  command
 pub struct ParamsWalletDecode {
-    /// Common config
-    pub config: CliConfig,
     /// wallet-id used to indentify the wallet
     pub wallet_id: String,
     /// tx_hex to decode
@@ -158,10 +226,31 @@ pub struct ParamsWalletDecode {
     pub partial_tx: Option<String>,
 }
 
+/// Arguments for the wallet batch-send command
+pub struct ParamsWalletBatchSend {
+    /// wallet-id used to indentify the wallet
+    pub wallet_id: String,
+    /// csv or json file with the payouts
+    pub file: PathBuf,
+    /// file to track the status of each row, defaults to `<file>.results.json`
+    pub results: Option<PathBuf>,
+    /// maximum number of outputs of each transaction, defaults to the headless limit
+    pub max_outputs: Option<usize>,
+    pub change_address: Option<String>,
+    /// send again the rows that may have been sent by an interrupted run
+    pub retry_unknown: bool,
+}
+
+/// Wait for a sent transaction to be confirmed
+pub struct ParamsWaitConfirmations {
+    /// How many blocks must confirm the transaction
+    pub confirmations: u64,
+    /// Give up after this long
+    pub timeout: Duration,
+}
+
 /// Arguments for the wallet tx-confirmation command
 pub struct ParamsWalletTxConfirmation {
-    /// Common config
-    pub config: CliConfig,
     /// wallet-id used to indentify the wallet
     pub wallet_id: String,
     /// tx_id to get confirmation blocks from
@@ -170,14 +259,12 @@ pub struct ParamsWalletTxConfirmation {
 
 /// Arguments for the wallet simple send command
 pub struct ParamsWalletSimpleSend {
-    /// Common config
-    pub config: CliConfig,
     /// wallet-id used to indentify the wallet
     pub wallet_id: String,
     /// Address to send tokens
     pub address: String,
     /// Amount of tokens to send
-    pub value: u32,
+    pub value: u64,
     /// Use this address if we require a change output
     pub change_address: Option<String>,
     /// Send this token, headless defaults to HTR
@@ -186,19 +273,15 @@ pub struct ParamsWalletSimpleSend {
 
 /// Arguments for the wallet send command
 pub struct ParamsWalletSend {
-    /// Common config
-    pub config: CliConfig,
     /// wallet-id used to indentify the wallet
     pub wallet_id: String,
-    /// json body to send, we perform no validations and send the body as is.
+    /// json body to send as is, see [`crate::send::build_send_tx_body`] to build a validated body.
     /// The headless may reject the call if the body is not valid json or a valid transaction.
     pub body: String,
 }
 
 /// Arguments for the wallet create token command
 pub struct ParamsWalletCreateToken {
-    /// Common config
-    pub config: CliConfig,
     /// wallet-id used to indentify the wallet
     pub wallet_id: String,
     /// Name of the token to create
@@ -206,7 +289,7 @@ pub struct ParamsWalletCreateToken {
     /// Symbol of the token to create
     pub symbol: String,
     /// amount of tokens to create
-    pub amount: u32,
+    pub amount: u64,
     /// Optionally specify the destination address
     pub address: Option<String>,
     /// Optionally specify the change address
@@ -228,14 +311,12 @@ pub struct ParamsWalletCreateToken {
 
 /// Arguments for the wallet mint tokens command
 pub struct ParamsWalletMintTokens {
-    /// Common config
-    pub config: CliConfig,
     /// wallet-id used to indentify the wallet
     pub wallet_id: String,
     /// Uid of the token
     pub token: String,
     /// amount of tokens to mint
-    pub amount: u32,
+    pub amount: u64,
     /// Optionally specify the destination address
     pub address: Option<String>,
     /// Optionally specify the change address
@@ -250,14 +331,12 @@ pub struct ParamsWalletMintTokens {
 
 /// Arguments for the wallet melt tokens command
 pub struct ParamsWalletMeltTokens {
-    /// Common config
-    pub config: CliConfig,
     /// wallet-id used to indentify the wallet
     pub wallet_id: String,
     /// Uid of the token
     pub token: String,
     /// amount of tokens to melt
-    pub amount: u32,
+    pub amount: u64,
     /// Optionally specify the destination address
     pub address: Option<String>,
     /// Optionally specify the deposit address
@@ -274,8 +353,6 @@ pub struct ParamsWalletMeltTokens {
 
 /// Arguments for the wallet utxo filter command
 pub struct ParamsWalletUtxoFilter {
-    /// Common config
-    pub config: CliConfig,
     /// wallet-id used to indentify the wallet
     pub wallet_id: String,
     /// Maximum number of utxos to return
@@ -285,19 +362,17 @@ pub struct ParamsWalletUtxoFilter {
     /// Filter for utxos on this address
     pub filter_address: Option<String>,
     /// Only utxos with amount smaller than this
-    pub amount_smaller_than: Option<u32>,
+    pub amount_smaller_than: Option<u64>,
     /// Only utxos with amount smaller than this
-    pub amount_bigger_than: Option<u32>,
+    pub amount_bigger_than: Option<u64>,
     /// Maximum sum of tokens from returned utxos
-    pub maximum_amount: Option<u32>,
+    pub maximum_amount: Option<u64>,
     /// Only return unlocked and ready to be used utxos, headless defaults to true
     pub only_available_utxos: Option<bool>,
 }
 
 /// Arguments for the wallet utxo consolidation command
 pub struct ParamsWalletUtxoConsolidation {
-    /// Common config
-    pub config: CliConfig,
     /// wallet-id used to indentify the wallet
     pub wallet_id: String,
     /// Maximum number of utxos to return
@@ -307,17 +382,15 @@ pub struct ParamsWalletUtxoConsolidation {
     /// Filter for utxos on this address
     pub filter_address: Option<String>,
     /// Only utxos with amount smaller than this
-    pub amount_smaller_than: Option<u32>,
+    pub amount_smaller_than: Option<u64>,
     /// Only utxos with amount smaller than this
-    pub amount_bigger_than: Option<u32>,
+    pub amount_bigger_than: Option<u64>,
     /// Maximum sum of tokens from returned utxos
-    pub maximum_amount: Option<u32>,
+    pub maximum_amount: Option<u64>,
 }
 
 /// Arguments for the wallet create nft command
 pub struct ParamsWalletCreateNft {
-    /// Common config
-    pub config: CliConfig,
     /// wallet-id used to indentify the wallet
     pub wallet_id: String,
     /// Name of the nft to create
@@ -325,7 +398,7 @@ pub struct ParamsWalletCreateNft {
     /// Symbol of the nft to create
     pub symbol: String,
     /// amount of nfts to create
-    pub amount: u32,
+    pub amount: u64,
     /// Data for the NFT's data output
     pub data: String,
     /// Optionally specify the destination address
@@ -347,17 +420,14 @@ pub struct ParamsWalletCreateNft {
 }
 
 pub struct ParamsWalletStop {
-    pub config: CliConfig,
     pub wallet_id: String,
 }
 
 pub struct ParamsCustomListTokens {
-    pub config: CliConfig,
     pub wallet_id: String,
 }
 
 pub struct ParamsCustomCurl {
-    pub config: CliConfig,
     pub wallet_id: String,
     pub post: bool,
     pub data: bool,
@@ -365,37 +435,76 @@ pub struct ParamsCustomCurl {
 }
 
 pub struct ParamsP2shTxProposalBuild {
-    pub config: CliConfig,
     pub wallet_id: String,
     pub body: String,
 }
 
 pub struct ParamsP2shTxProposalBuildSimpleSendTokens {
-    pub config: CliConfig,
     pub wallet_id: String,
     pub address: String,
-    pub value: u32,
+    pub value: u64,
     pub token: Option<String>,
     pub change_address: Option<String>,
     pub mark_inputs_as_used: Option<bool>,
 }
 
 pub struct ParamsP2shTxProposalGetMySignatures {
-    pub config: CliConfig,
     pub wallet_id: String,
     pub tx_hex: String,
 }
 pub struct ParamsP2shTxProposalSign {
-    pub config: CliConfig,
     pub wallet_id: String,
     pub tx_hex: String,
     pub signatures: Vec<String>,
 }
 
+/// Arguments for the p2sh collect command
+pub struct ParamsP2shTxProposalCollect {
+    /// wallet-id of the coordinator, which signs the proposal with the collected signatures
+    pub wallet_id: String,
+    pub tx_hex: String,
+    /// wallets (on the same headless) to get the signatures from
+    pub signer_wallets: Vec<String>,
+    /// push the signed transaction to the network
+    pub push: bool,
+}
+
+/// Arguments for the p2sh proposal new command
+pub struct ParamsP2shProposalNew {
+    /// wallet-id used to indentify the wallet
+    pub wallet_id: String,
+    /// proposal file to create
+    pub file: PathBuf,
+    /// JSON body of a tx proposal to build, used when there is no `tx_hex`
+    pub body: Option<String>,
+    /// tx proposal already built
+    pub tx_hex: Option<String>,
+    /// signatures needed to send the transaction (M)
+    pub signatures_required: u32,
+    /// participants of the multisig wallet (N)
+    pub signers: u32,
+}
+
+/// Arguments for the p2sh proposal add-signature command
+pub struct ParamsP2shProposalAddSignature {
+    /// wallet-id used to indentify the wallet
+    pub wallet_id: String,
+    pub file: PathBuf,
+    /// signatures of another signer, the signatures of this wallet are fetched if not set
+    pub signatures: Option<String>,
+}
+
+/// Arguments for the p2sh proposal finalize command
+pub struct ParamsP2shProposalFinalize {
+    /// wallet-id used to indentify the wallet
+    pub wallet_id: String,
+    pub file: PathBuf,
+    /// push the signed transaction to the network
+    pub push: bool,
+}
+
 /// Arguments for the wallet create token command
 pub struct ParamsWalletP2shTxProposalCreateToken {
-    /// Common config
-    pub config: CliConfig,
     /// wallet-id used to indentify the wallet
     pub wallet_id: String,
     /// Name of the token to create
@@ -403,7 +512,7 @@ pub struct ParamsWalletP2shTxProposalCreateToken {
     /// Symbol of the token to create
     pub symbol: String,
     /// amount of tokens to create
-    pub amount: u32,
+    pub amount: u64,
     /// Optionally specify the destination address
     pub address: Option<String>,
     /// Optionally specify the change address
@@ -426,14 +535,12 @@ pub struct ParamsWalletP2shTxProposalCreateToken {
 
 /// Arguments for the wallet P2SH mint tokens command
 pub struct ParamsWalletP2shTxProposalMintTokens {
-    /// Common config
-    pub config: CliConfig,
     /// wallet-id used to indentify the wallet
     pub wallet_id: String,
     /// Uid of the token
     pub token: String,
     /// amount of tokens to mint
-    pub amount: u32,
+    pub amount: u64,
     /// Optionally specify the destination address
     pub address: Option<String>,
     /// Optionally specify the change address
@@ -450,14 +557,12 @@ pub struct ParamsWalletP2shTxProposalMintTokens {
 
 /// Arguments for the wallet P2SH Melt tokens command
 pub struct ParamsWalletP2shTxProposalMeltTokens {
-    /// Common config
-    pub config: CliConfig,
     /// wallet-id used to indentify the wallet
     pub wallet_id: String,
     /// Uid of the token
     pub token: String,
     /// amount of tokens to melt
-    pub amount: u32,
+    pub amount: u64,
     /// Optionally specify the destination address
     pub address: Option<String>,
     /// Optionally specify the change address
@@ -0,0 +1,568 @@
+//! Multisig tx proposals shared between the signers as a JSON file.
+//!
+//! The coordinator creates the file with the tx proposal, each signer adds
+//! the signatures of their wallet to it (the file is all they need, on any
+//! machine) and once M of the N participants have signed the proposal can be
+//! finalized, i.e. signed and optionally pushed by the coordinator.
+//!
+//! Signers can also check a proposal against an [`OutputPolicy`] with the
+//! outputs they expect before signing it.
+//!
+//! ```json
+//! {
+//!   "tx_hex": "0001...",
+//!   "summary": { "tokens": [], "inputs": [...], "outputs": [...] },
+//!   "signatures_required": 2,
+//!   "signers": 3,
+//!   "signatures": { "xpub6...": "xpub6...|0:3045..." }
+//! }
+//! ```
+
+use crate::amount::{Amount, AmountArg};
+use crate::data::DecodedTx;
+use crate::error::HeadlessCliError;
+use crate::utils::{read_input_file, read_json_file, write_json_file};
+use crate::validation::HTR_UID;
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/////////////////////////////////////////// Proposals
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Proposal {
+    /// The unsigned transaction, the signatures are only valid for it
+    pub tx_hex: String,
+    /// The transaction decoded by the headless when the proposal was created
+    pub summary: DecodedTx,
+    /// Signatures needed to send the transaction (M)
+    pub signatures_required: u32,
+    /// Participants of the multisig wallet (N)
+    pub signers: u32,
+    /// Serialized signatures (as returned by `get-my-signatures`) keyed by signer pubkey
+    #[serde(default)]
+    pub signatures: BTreeMap<String, String>,
+    /// The transaction signed with the collected signatures, set when finalized
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signed_tx_hex: Option<String>,
+    /// Set when the finalized transaction was pushed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_id: Option<String>,
+}
+
+/// Progress of a proposal towards its signature threshold.
+#[derive(Serialize, Debug)]
+pub struct ProposalStatus {
+    pub signatures_required: u32,
+    pub signers: u32,
+    pub collected: usize,
+    pub missing: usize,
+    /// Enough signatures to finalize the proposal
+    pub ready: bool,
+    /// Pubkeys of the signers that already signed
+    pub signed_by: Vec<String>,
+    pub finalized: bool,
+    pub tx_id: Option<String>,
+}
+
+/// Pubkey of the signer of serialized signatures (`pubkey|index:signature|...`).
+///
+/// # Examples
+///
+/// ```
+/// use headless_cli::proposal::signer_pubkey;
+///
+/// assert_eq!(signer_pubkey("xpub6abc|0:3045|1:3046").unwrap(), "xpub6abc");
+/// assert!(signer_pubkey("|0:3045").is_err());
+/// assert!(signer_pubkey("xpub6abc|3045").is_err());
+/// ```
+pub fn signer_pubkey(signatures: &str) -> Result<&str, HeadlessCliError> {
+    let invalid = || {
+        HeadlessCliError::InvalidInput(format!(
+            "invalid signatures {:?}, expected `pubkey|index:signature|...`",
+            signatures
+        ))
+    };
+    let mut parts = signatures.trim().split('|');
+    let pubkey = parts.next().filter(|pubkey| !pubkey.is_empty());
+    let pubkey = pubkey.ok_or_else(invalid)?;
+    for part in parts {
+        let (index, signature) = part.split_once(':').ok_or_else(invalid)?;
+        if index.parse::<u8>().is_err() || signature.is_empty() {
+            return Err(invalid());
+        }
+    }
+    Ok(pubkey)
+}
+
+impl Proposal {
+    /// Start a proposal with no signatures, requires `1 <= signatures_required <= signers`.
+    pub fn new(
+        tx_hex: String,
+        summary: DecodedTx,
+        signatures_required: u32,
+        signers: u32,
+    ) -> Result<Self, HeadlessCliError> {
+        if signatures_required == 0 || signatures_required > signers {
+            return Err(HeadlessCliError::InvalidInput(format!(
+                "invalid multisig {}-of-{}",
+                signatures_required, signers
+            )));
+        }
+        Ok(Proposal {
+            tx_hex,
+            summary,
+            signatures_required,
+            signers,
+            signatures: BTreeMap::new(),
+            signed_tx_hex: None,
+            tx_id: None,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, HeadlessCliError> {
+        read_json_file(path, "a proposal file")
+    }
+
+    /// Write the proposal, replacing the file atomically.
+    pub fn save(&self, path: &Path) -> Result<(), HeadlessCliError> {
+        write_json_file(path, self)
+    }
+
+    /// Add the serialized signatures of a signer, returns the signer pubkey.
+    ///
+    /// Adding the same signatures again does nothing, a signer cannot replace
+    /// its signatures and no signatures are accepted once finalized.
+    pub fn add_signatures(&mut self, signatures: &str) -> Result<String, HeadlessCliError> {
+        if self.is_finalized() {
+            return Err(HeadlessCliError::InvalidInput(
+                "the proposal was already finalized".to_string(),
+            ));
+        }
+        let signatures = signatures.trim();
+        let pubkey = signer_pubkey(signatures)?.to_string();
+        match self.signatures.get(&pubkey) {
+            Some(existing) if existing == signatures => return Ok(pubkey),
+            Some(_) => {
+                return Err(HeadlessCliError::InvalidInput(format!(
+                    "{} already signed the proposal with other signatures",
+                    pubkey
+                )))
+            }
+            None if self.signatures.len() >= self.signers as usize => {
+                return Err(HeadlessCliError::InvalidInput(format!(
+                    "the proposal already has signatures of all the {} signers",
+                    self.signers
+                )))
+            }
+            None => {}
+        }
+        self.signatures
+            .insert(pubkey.clone(), signatures.to_string());
+        Ok(pubkey)
+    }
+
+    /// The collected signatures, in the format of the sign endpoints.
+    pub fn collected_signatures(&self) -> Vec<String> {
+        self.signatures.values().cloned().collect()
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.signatures.len() >= self.signatures_required as usize
+    }
+
+    /// Signed or pushed, no more signatures are needed
+    pub fn is_finalized(&self) -> bool {
+        self.signed_tx_hex.is_some() || self.tx_id.is_some()
+    }
+
+    pub fn status(&self) -> ProposalStatus {
+        ProposalStatus {
+            signatures_required: self.signatures_required,
+            signers: self.signers,
+            collected: self.signatures.len(),
+            missing: (self.signatures_required as usize).saturating_sub(self.signatures.len()),
+            ready: self.is_ready(),
+            signed_by: self.signatures.keys().cloned().collect(),
+            finalized: self.is_finalized(),
+            tx_id: self.tx_id.clone(),
+        }
+    }
+}
+
+/////////////////////////////////////////// Signer policy
+
+/// Outputs a signer expects on the proposals it signs.
+///
+/// Same shape as the outputs of a tx proposal body:
+/// `{ "outputs": [{ "address": "W...", "value": "1.00", "token": "00" }] }`,
+/// values follow the rules of [`AmountArg`] and the token defaults to HTR.
+#[derive(Debug)]
+pub struct OutputPolicy {
+    pub outputs: Vec<ExpectedOutput>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpectedOutput {
+    pub address: String,
+    /// Value in units
+    pub value: u64,
+    pub token: Option<String>,
+}
+
+/// The policy as written on the file, values may be numbers or strings.
+#[derive(Deserialize)]
+struct PolicyFile {
+    outputs: Vec<PolicyOutput>,
+}
+
+#[derive(Deserialize)]
+struct PolicyOutput {
+    address: String,
+    value: Value,
+    token: Option<String>,
+}
+
+impl ExpectedOutput {
+    fn describe(&self) -> String {
+        let token = match self.token.as_deref() {
+            None | Some(HTR_UID) => "HTR",
+            Some(token) => token,
+        };
+        format!(
+            "{} {} to {}",
+            Amount::from_units(self.value),
+            token,
+            self.address
+        )
+    }
+}
+
+impl OutputPolicy {
+    pub fn load(path: &Path, raw_units: bool) -> Result<Self, HeadlessCliError> {
+        Self::parse(&read_input_file(path)?, raw_units).map_err(|err| match err {
+            HeadlessCliError::InvalidInput(message) => HeadlessCliError::InvalidInput(format!(
+                "{} is not a policy file: {}",
+                path.display(),
+                message
+            )),
+            err => err,
+        })
+    }
+
+    /// Parse a policy, values without `raw_units` need a decimal point or the token symbol.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use headless_cli::proposal::OutputPolicy;
+    ///
+    /// let json = r#"{"outputs": [{"address": "WewDeXWyvHP7jJTs7tjLoQfoB72LLxJQqN", "value": "1.00"}]}"#;
+    /// assert_eq!(OutputPolicy::parse(json, false).unwrap().outputs[0].value, 100);
+    ///
+    /// let json = r#"{"outputs": [{"address": "WewDeXWyvHP7jJTs7tjLoQfoB72LLxJQqN", "value": 100}]}"#;
+    /// assert!(OutputPolicy::parse(json, false).is_err());
+    /// assert_eq!(OutputPolicy::parse(json, true).unwrap().outputs[0].value, 100);
+    /// ```
+    pub fn parse(content: &str, raw_units: bool) -> Result<Self, HeadlessCliError> {
+        let file: PolicyFile = serde_json::from_str(content)
+            .map_err(|err| HeadlessCliError::InvalidInput(err.to_string()))?;
+        let mut outputs = vec![];
+        for (i, output) in file.outputs.into_iter().enumerate() {
+            let value = match &output.value {
+                Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+            // Custom token symbols are not known offline, only HTR can have one
+            let token = output.token.as_deref().unwrap_or(HTR_UID);
+            let value = value
+                .parse::<AmountArg>()
+                .map_err(HeadlessCliError::InvalidInput)
+                .and_then(|amount| amount.to_token_amount(raw_units, Some(token), None))
+                .map_err(|err| match err {
+                    HeadlessCliError::InvalidInput(message) => {
+                        HeadlessCliError::InvalidInput(format!("output {}: {}", i + 1, message))
+                    }
+                    err => err,
+                })?;
+            outputs.push(ExpectedOutput {
+                address: output.address,
+                value: value.units(),
+                token: output.token,
+            });
+        }
+        Ok(OutputPolicy { outputs })
+    }
+
+    /// Check that the transaction has every expected output and that the
+    /// other outputs go back to the wallet (the change).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use headless_cli::data::DecodedTx;
+    /// use headless_cli::proposal::OutputPolicy;
+    ///
+    /// let policy = OutputPolicy::parse(
+    ///     r#"{"outputs": [{"address": "WewDeXWyvHP7jJTs7tjLoQfoB72LLxJQqN", "value": "1.00"}]}"#,
+    ///     false,
+    /// ).unwrap();
+    /// let tx: DecodedTx = serde_json::from_str(r#"{"outputs": [
+    ///     {"value": 100, "tokenData": 0, "decoded": {"address": "WewDeXWyvHP7jJTs7tjLoQfoB72LLxJQqN"}},
+    ///     {"value": 5, "tokenData": 0, "decoded": {"address": "wcUZ6J7t2B1s8bqRYiyuZAftcdCGRSiiau"}, "mine": true}
+    /// ]}"#).unwrap();
+    /// assert!(policy.check(&tx).is_ok());
+    ///
+    /// let tx: DecodedTx = serde_json::from_str(r#"{"outputs": [
+    ///     {"value": 100, "tokenData": 0, "decoded": {"address": "WmtWgtk5GxdcDKwjNwmXXn74nQWTPWhKfx"}}
+    /// ]}"#).unwrap();
+    /// assert!(policy.check(&tx).is_err());
+    /// ```
+    pub fn check(&self, tx: &DecodedTx) -> Result<(), HeadlessCliError> {
+        let mut missing = self.outputs.clone();
+        let mut errors = vec![];
+        for output in tx.outputs.iter() {
+            let actual = ExpectedOutput {
+                address: output
+                    .decoded
+                    .as_ref()
+                    .and_then(|decoded| decoded.address.clone())
+                    .unwrap_or_default(),
+                value: output.value,
+                token: Some(tx.output_token(output).unwrap_or(HTR_UID).to_string()),
+            };
+            let expected = missing.iter().position(|expected| {
+                expected.address == actual.address
+                    && expected.value == actual.value
+                    && expected.token.as_deref().unwrap_or(HTR_UID)
+                        == actual.token.as_deref().unwrap_or(HTR_UID)
+            });
+            match expected {
+                Some(index) => {
+                    missing.remove(index);
+                }
+                None if output.mine == Some(true) => {}
+                None => errors.push(format!("unexpected output {}", actual.describe())),
+            }
+        }
+        for expected in missing.iter() {
+            errors.push(format!("missing output {}", expected.describe()));
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(HeadlessCliError::PolicyViolation(errors.join(", "))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::tests::TempFile;
+
+    const ADDRESS: &str = "WewDeXWyvHP7jJTs7tjLoQfoB72LLxJQqN";
+    const OTHER_ADDRESS: &str = "WmtWgtk5GxdcDKwjNwmXXn74nQWTPWhKfx";
+    const TOKEN: &str = "00000000f76262bb1cca969d952ac2f0e85f88ec34c31f26a13eb3c31e29d4ed";
+
+    fn decoded_tx(outputs: serde_json::Value) -> DecodedTx {
+        serde_json::from_value(serde_json::json!({ "tokens": [TOKEN], "outputs": outputs }))
+            .unwrap()
+    }
+
+    fn output(address: &str, value: u64, token_data: u8, mine: bool) -> serde_json::Value {
+        serde_json::json!({
+            "value": value,
+            "tokenData": token_data,
+            "decoded": { "address": address },
+            "mine": mine,
+        })
+    }
+
+    fn parse_policy(outputs: serde_json::Value, raw_units: bool) -> OutputPolicy {
+        let json = serde_json::json!({ "outputs": outputs }).to_string();
+        OutputPolicy::parse(&json, raw_units).unwrap()
+    }
+
+    fn violation(policy: &OutputPolicy, tx: &DecodedTx) -> String {
+        match policy.check(tx) {
+            Err(HeadlessCliError::PolicyViolation(message)) => message,
+            result => panic!("expected a policy violation, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn check_accepts_expected_outputs_and_change() {
+        let policy = parse_policy(
+            serde_json::json!([
+                { "address": ADDRESS, "value": "1.00" },
+                { "address": ADDRESS, "value": "2.50", "token": TOKEN },
+            ]),
+            false,
+        );
+        let tx = decoded_tx(serde_json::json!([
+            output(ADDRESS, 250, 1, false),
+            output(ADDRESS, 100, 0, false),
+            output(OTHER_ADDRESS, 7, 0, true),
+        ]));
+        assert!(policy.check(&tx).is_ok());
+    }
+
+    #[test]
+    fn check_reads_raw_units() {
+        let policy = parse_policy(
+            serde_json::json!([{ "address": ADDRESS, "value": 100 }]),
+            true,
+        );
+        assert!(policy
+            .check(&decoded_tx(serde_json::json!([output(
+                ADDRESS, 100, 0, false
+            )])))
+            .is_ok());
+    }
+
+    #[test]
+    fn check_rejects_unexpected_outputs() {
+        let policy = parse_policy(
+            serde_json::json!([{ "address": ADDRESS, "value": "1.00" }]),
+            false,
+        );
+        let tx = decoded_tx(serde_json::json!([
+            output(ADDRESS, 100, 0, false),
+            output(OTHER_ADDRESS, 5, 0, false),
+        ]));
+        assert_eq!(
+            violation(&policy, &tx),
+            format!("unexpected output 0.05 HTR to {}", OTHER_ADDRESS)
+        );
+    }
+
+    #[test]
+    fn check_rejects_missing_outputs() {
+        let policy = parse_policy(
+            serde_json::json!([{ "address": ADDRESS, "value": "1.00" }]),
+            false,
+        );
+        let tx = decoded_tx(serde_json::json!([output(ADDRESS, 100, 0, true)]));
+        assert!(policy.check(&tx).is_ok());
+
+        let tx = decoded_tx(serde_json::json!([output(OTHER_ADDRESS, 100, 0, true)]));
+        assert_eq!(
+            violation(&policy, &tx),
+            format!("missing output 1.00 HTR to {}", ADDRESS)
+        );
+    }
+
+    #[test]
+    fn check_matches_value_and_token() {
+        let policy = parse_policy(
+            serde_json::json!([{ "address": ADDRESS, "value": "1.00" }]),
+            false,
+        );
+
+        let tx = decoded_tx(serde_json::json!([output(ADDRESS, 101, 0, false)]));
+        assert_eq!(
+            violation(&policy, &tx),
+            format!(
+                "unexpected output 1.01 HTR to {0}, missing output 1.00 HTR to {0}",
+                ADDRESS
+            )
+        );
+
+        let tx = decoded_tx(serde_json::json!([output(ADDRESS, 100, 1, false)]));
+        assert_eq!(
+            violation(&policy, &tx),
+            format!(
+                "unexpected output 1.00 {1} to {0}, missing output 1.00 HTR to {0}",
+                ADDRESS, TOKEN
+            )
+        );
+    }
+
+    #[test]
+    fn parse_rejects_ambiguous_values_and_custom_token_symbols() {
+        let json = serde_json::json!({ "outputs": [{ "address": ADDRESS, "value": 100 }] });
+        assert!(OutputPolicy::parse(&json.to_string(), false).is_err());
+
+        let json = serde_json::json!({
+            "outputs": [{ "address": ADDRESS, "value": "1.00HTR", "token": TOKEN }]
+        });
+        assert!(OutputPolicy::parse(&json.to_string(), false).is_err());
+    }
+
+    fn proposal(signatures_required: u32, signers: u32) -> Proposal {
+        let summary = decoded_tx(serde_json::json!([output(ADDRESS, 100, 0, false)]));
+        Proposal::new("0001".to_string(), summary, signatures_required, signers).unwrap()
+    }
+
+    #[test]
+    fn new_rejects_invalid_thresholds() {
+        for (m, n) in [(0, 3), (4, 3)] {
+            let summary = decoded_tx(serde_json::json!([]));
+            assert!(Proposal::new("0001".to_string(), summary, m, n).is_err());
+        }
+    }
+
+    #[test]
+    fn adding_the_same_signatures_twice_does_nothing() {
+        let mut proposal = proposal(2, 3);
+        assert_eq!(proposal.add_signatures("xpubA|0:3045aa").unwrap(), "xpubA");
+        assert_eq!(
+            proposal.add_signatures(" xpubA|0:3045aa\n").unwrap(),
+            "xpubA"
+        );
+        assert_eq!(proposal.collected_signatures(), vec!["xpubA|0:3045aa"]);
+        assert!(!proposal.is_ready());
+    }
+
+    #[test]
+    fn signers_cannot_replace_their_signatures() {
+        let mut proposal = proposal(2, 3);
+        proposal.add_signatures("xpubA|0:3045aa").unwrap();
+        let err = proposal.add_signatures("xpubA|0:3045bb").unwrap_err();
+        assert!(err.to_string().contains("xpubA already signed"), "{}", err);
+        assert_eq!(proposal.collected_signatures(), vec!["xpubA|0:3045aa"]);
+    }
+
+    #[test]
+    fn no_more_signers_than_the_participants() {
+        let mut proposal = proposal(1, 2);
+        proposal.add_signatures("xpubA|0:3045aa").unwrap();
+        proposal.add_signatures("xpubB|0:3045bb").unwrap();
+        assert!(proposal.add_signatures("xpubC|0:3045cc").is_err());
+        assert_eq!(proposal.status().signed_by, vec!["xpubA", "xpubB"]);
+        assert!(proposal.is_ready());
+    }
+
+    #[test]
+    fn no_signatures_once_finalized() {
+        let mut proposal = proposal(1, 2);
+        proposal.add_signatures("xpubA|0:3045aa").unwrap();
+        proposal.signed_tx_hex = Some("0001signed".to_string());
+        assert!(proposal.is_finalized());
+        let err = proposal.add_signatures("xpubB|0:3045bb").unwrap_err();
+        assert!(err.to_string().contains("already finalized"), "{}", err);
+
+        // not even the signatures it already has
+        assert!(proposal.add_signatures("xpubA|0:3045aa").is_err());
+    }
+
+    #[test]
+    fn proposals_round_trip_through_the_file() {
+        let file = TempFile::new("proposal.json");
+        let mut proposal = proposal(2, 3);
+        proposal.add_signatures("xpubA|0:3045aa").unwrap();
+        proposal.save(&file.0).unwrap();
+
+        let loaded = Proposal::load(&file.0).unwrap();
+        assert_eq!(loaded.signatures, proposal.signatures);
+        assert_eq!(loaded.summary.outputs.len(), 1);
+        assert_eq!(
+            (loaded.signatures_required, loaded.signers),
+            (proposal.signatures_required, proposal.signers)
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use crate::error::HeadlessCliError;
+
+use serde_json::Value;
+
+/////////////////////////////////////////// Query
+
+/// Select a field of a json response with a dotted path.
+///
+/// Keys are separated by dots, list items are selected by their index
+/// (`utxos.0.amount` or `utxos[0].amount`) and `*` selects the field of
+/// every item of a list (`utxos.*.amount`). A leading dot is optional.
+///
+/// # Examples
+///
+/// ```
+/// use headless_cli::query::query;
+/// use serde_json::json;
+///
+/// let response = json!({
+///     "total_amount_available": 300,
+///     "utxos": [
+///         { "tx_id": "00ab", "amount": 100 },
+///         { "tx_id": "00cd", "amount": 200 },
+///     ],
+/// });
+///
+/// assert_eq!(query(&response, "total_amount_available").unwrap(), json!(300));
+/// assert_eq!(query(&response, ".utxos.1.tx_id").unwrap(), json!("00cd"));
+/// assert_eq!(query(&response, "utxos[0].amount").unwrap(), json!(100));
+/// assert_eq!(query(&response, "utxos.*.amount").unwrap(), json!([100, 200]));
+/// assert!(query(&response, "utxos.2").is_err());
+/// ```
+pub fn query(value: &Value, path: &str) -> Result<Value, HeadlessCliError> {
+    let path = path
+        .strip_prefix('.')
+        .unwrap_or(path)
+        .replace('[', ".")
+        .replace(']', "");
+    if path.is_empty() {
+        return Ok(value.clone());
+    }
+
+    let keys: Vec<&str> = path.split('.').collect();
+    select(value, &keys).ok_or_else(|| {
+        HeadlessCliError::InvalidInput(format!("{} not found on the response", path))
+    })
+}
+
+fn select(value: &Value, keys: &[&str]) -> Option<Value> {
+    let (key, rest) = match keys.split_first() {
+        Some(split) => split,
+        None => return Some(value.clone()),
+    };
+
+    match (value, *key) {
+        (Value::Array(list), "*") => list
+            .iter()
+            .map(|item| select(item, rest))
+            .collect::<Option<Vec<Value>>>()
+            .map(Value::Array),
+        (Value::Array(list), index) => select(list.get(index.parse::<usize>().ok()?)?, rest),
+        (Value::Object(map), key) => select(map.get(key)?, rest),
+        _ => None,
+    }
+}
@@ -0,0 +1,505 @@
+use crate::amount::{Amount, AmountArg};
+use crate::data::BalanceResponse;
+use crate::error::HeadlessCliError;
+use crate::utils::HashMapValue;
+use crate::validation::{validate_address, validate_token_uid, validate_tx_id, HTR_UID};
+
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+use serde_json::{Map, Value};
+
+/////////////////////////////////////////// Send builder
+
+/// An output typed on the command line as `ADDRESS:AMOUNT[:TOKEN][:TIMELOCK]`.
+///
+/// The amount follows the rules of [`AmountArg`] and the timelock is a unix timestamp.
+///
+/// # Examples
+///
+/// ```
+/// use headless_cli::send::OutputSpec;
+///
+/// let spec: OutputSpec = "WewDeXWyvHP7jJTs7tjLoQfoB72LLxJQqN:12.34".parse().unwrap();
+/// assert_eq!(spec.address, "WewDeXWyvHP7jJTs7tjLoQfoB72LLxJQqN");
+/// assert_eq!(spec.token, None);
+///
+/// let spec: OutputSpec = format!("WewDeXWyvHP7jJTs7tjLoQfoB72LLxJQqN:5:{}:1700000000", "ab".repeat(32))
+///     .parse()
+///     .unwrap();
+/// assert_eq!(spec.timelock, Some(1700000000));
+///
+/// assert!("WewDeXWyvHP7jJTs7tjLoQfoB72LLxJQqN".parse::<OutputSpec>().is_err());
+/// assert!("not-an-address:10".parse::<OutputSpec>().is_err());
+/// assert!("WewDeXWyvHP7jJTs7tjLoQfoB72LLxJQqN:10:HTR".parse::<OutputSpec>().is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputSpec {
+    pub address: String,
+    pub amount: AmountArg,
+    pub token: Option<String>,
+    pub timelock: Option<u64>,
+}
+
+impl OutputSpec {
+    /// Resolve the amount to units, zero amounts are rejected.
+    ///
+    /// # Arguments
+    ///
+    /// * `raw_units` - Interpret the amount as units instead of a decimal amount
+    /// * `default_token` - Token of the output when the spec has none (e.g. the profile token)
+    /// * `token_symbol` - Symbol of the custom token of the output, if known
+    ///
+    pub fn to_output(
+        &self,
+        raw_units: bool,
+        default_token: Option<&str>,
+        token_symbol: Option<&str>,
+    ) -> Result<SendOutput, HeadlessCliError> {
+        let token = self.token.as_deref().or(default_token);
+        let value = self
+            .amount
+            .to_token_amount(raw_units, token, token_symbol)?
+            .units();
+        if value == 0 {
+            return Err(HeadlessCliError::InvalidInput(format!(
+                "output to {} has a zero amount",
+                self.address
+            )));
+        }
+
+        Ok(SendOutput::Value {
+            address: self.address.clone(),
+            value,
+            token: token.map(str::to_string),
+            timelock: self.timelock,
+        })
+    }
+}
+
+impl FromStr for OutputSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        if !(2..=4).contains(&parts.len()) {
+            return Err(format!(
+                "expected ADDRESS:AMOUNT[:TOKEN][:TIMELOCK], got {}",
+                s
+            ));
+        }
+
+        validate_address(parts[0]).map_err(|err| err.to_string())?;
+        let amount = parts[1].parse::<AmountArg>()?;
+
+        let token = match parts.get(2) {
+            Some(token) if !token.is_empty() => {
+                validate_token_uid(token).map_err(|err| err.to_string())?;
+                Some(token.to_string())
+            }
+            _ => None,
+        };
+
+        let timelock = parts
+            .get(3)
+            .map(|timelock| {
+                timelock
+                    .parse::<u64>()
+                    .map_err(|_| format!("invalid timelock: {}", timelock))
+            })
+            .transpose()?;
+
+        Ok(OutputSpec {
+            address: parts[0].to_string(),
+            amount,
+            token,
+            timelock,
+        })
+    }
+}
+
+/// An input typed on the command line as `TXID:INDEX`.
+///
+/// # Examples
+///
+/// ```
+/// use headless_cli::send::InputSpec;
+///
+/// let spec: InputSpec = format!("{}:1", "ab".repeat(32)).parse().unwrap();
+/// assert_eq!(spec.index, 1);
+/// assert!("abcd:1".parse::<InputSpec>().is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InputSpec {
+    pub hash: String,
+    pub index: u32,
+}
+
+impl FromStr for InputSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (hash, index) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected TXID:INDEX, got {}", s))?;
+
+        validate_tx_id(hash).map_err(|err| err.to_string())?;
+        let index = index
+            .parse::<u32>()
+            .map_err(|_| format!("invalid output index: {}", index))?;
+
+        Ok(InputSpec {
+            hash: hash.to_string(),
+            index,
+        })
+    }
+}
+
+/// An output of the `send-tx` body with the amount in units.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SendOutput {
+    Value {
+        address: String,
+        value: u64,
+        token: Option<String>,
+        timelock: Option<u64>,
+    },
+    /// Data output (costs 0.01 HTR)
+    Data(String),
+}
+
+impl SendOutput {
+    fn to_map(&self) -> HashMap<String, HashMapValue> {
+        let mut map: HashMap<String, HashMapValue> = HashMap::new();
+        match self {
+            SendOutput::Value {
+                address,
+                value,
+                token,
+                timelock,
+            } => {
+                map.insert("address".to_string(), address.clone().into());
+                map.insert("value".to_string(), (*value).into());
+                if let Some(token) = token {
+                    map.insert("token".to_string(), token.clone().into());
+                }
+                if let Some(timelock) = timelock {
+                    map.insert("timelock".to_string(), (*timelock).into());
+                }
+            }
+            SendOutput::Data(data) => {
+                map.insert("type".to_string(), "data".to_string().into());
+                map.insert("data".to_string(), data.clone().into());
+            }
+        }
+        map
+    }
+}
+
+/// Build the json body of `/wallet/send-tx`.
+///
+/// # Examples
+///
+/// ```
+/// use headless_cli::send::{build_send_tx_body, SendOutput};
+/// use serde_json::{json, Value};
+///
+/// let outputs = vec![
+///     SendOutput::Value {
+///         address: "WewDeXWyvHP7jJTs7tjLoQfoB72LLxJQqN".to_string(),
+///         value: 1234,
+///         token: None,
+///         timelock: None,
+///     },
+///     SendOutput::Data("payout #1".to_string()),
+/// ];
+/// let body = build_send_tx_body(&outputs, &[], None).unwrap();
+/// assert_eq!(
+///     serde_json::from_str::<Value>(&body).unwrap(),
+///     json!({ "outputs": [
+///         { "address": "WewDeXWyvHP7jJTs7tjLoQfoB72LLxJQqN", "value": 1234 },
+///         { "type": "data", "data": "payout #1" },
+///     ]})
+/// );
+///
+/// assert!(build_send_tx_body(&[], &[], None).is_err());
+/// ```
+pub fn build_send_tx_body(
+    outputs: &[SendOutput],
+    inputs: &[InputSpec],
+    change_address: Option<&str>,
+) -> Result<String, HeadlessCliError> {
+    if outputs.is_empty() {
+        return Err(HeadlessCliError::InvalidInput(
+            "at least one output is required".to_string(),
+        ));
+    }
+
+    let mut map: HashMap<&str, HashMapValue> = HashMap::new();
+    let outputs: Vec<HashMapValue> = outputs
+        .iter()
+        .map(|output| output.to_map().into())
+        .collect();
+    map.insert("outputs", outputs.into());
+
+    if !inputs.is_empty() {
+        let inputs: Vec<HashMapValue> = inputs
+            .iter()
+            .map(|input| {
+                let mut input_map: HashMap<String, HashMapValue> = HashMap::new();
+                input_map.insert("hash".to_string(), input.hash.clone().into());
+                input_map.insert("index".to_string(), input.index.into());
+                input_map.into()
+            })
+            .collect();
+        map.insert("inputs", inputs.into());
+    }
+
+    if let Some(change_address) = change_address {
+        validate_address(change_address)?;
+        map.insert("change_address", change_address.to_string().into());
+    }
+
+    Ok(serde_json::to_string(&map)?)
+}
+
+/// Convert a `send-tx` body to the body of the P2SH tx proposal endpoint.
+///
+/// Inputs are keyed `txId` instead of `hash` and the fields the endpoint does
+/// not know are left out, data outputs and query inputs are not supported.
+///
+/// # Examples
+///
+/// ```
+/// use headless_cli::send::tx_proposal_body;
+/// use serde_json::json;
+///
+/// let body = json!({
+///     "outputs": [{ "address": "WewDeXWyvHP7jJTs7tjLoQfoB72LLxJQqN", "value": 100 }],
+///     "inputs": [{ "hash": "ab".repeat(32), "index": 1 }],
+///     "change_address": "WmtWgtk5GxdcDKwjNwmXXn74nQWTPWhKfx",
+/// });
+/// let proposal = tx_proposal_body(&body.to_string()).unwrap();
+/// assert_eq!(
+///     serde_json::Value::Object(proposal),
+///     json!({
+///         "outputs": [{ "address": "WewDeXWyvHP7jJTs7tjLoQfoB72LLxJQqN", "value": 100 }],
+///         "inputs": [{ "txId": "ab".repeat(32), "index": 1 }],
+///         "change_address": "WmtWgtk5GxdcDKwjNwmXXn74nQWTPWhKfx",
+///     })
+/// );
+///
+/// let body = json!({ "outputs": [{ "type": "data", "data": "memo" }] });
+/// assert!(tx_proposal_body(&body.to_string()).is_err());
+/// ```
+pub fn tx_proposal_body(body: &str) -> Result<Map<String, Value>, HeadlessCliError> {
+    let body: Value = serde_json::from_str(body)?;
+    let unsupported = |what: &str| {
+        HeadlessCliError::InvalidInput(format!("{} are not supported by tx proposals", what))
+    };
+    let mut proposal = Map::new();
+
+    let mut outputs = vec![];
+    for output in body["outputs"].as_array().into_iter().flatten() {
+        if output["type"] == "data" {
+            return Err(unsupported("data outputs"));
+        }
+        let mut map = Map::new();
+        for field in ["address", "value", "token", "timelock"] {
+            if let Some(value) = output.get(field) {
+                map.insert(field.to_string(), value.clone());
+            }
+        }
+        outputs.push(Value::Object(map));
+    }
+    proposal.insert("outputs".to_string(), outputs.into());
+
+    if let Some(inputs) = body["inputs"].as_array() {
+        let mut proposal_inputs = vec![];
+        for input in inputs.iter() {
+            if input.get("type").is_some() {
+                return Err(unsupported("query inputs"));
+            }
+            let mut map = Map::new();
+            map.insert("txId".to_string(), input["hash"].clone());
+            map.insert("index".to_string(), input["index"].clone());
+            proposal_inputs.push(Value::Object(map));
+        }
+        proposal.insert("inputs".to_string(), proposal_inputs.into());
+    }
+
+    if let Some(change_address) = body.get("change_address") {
+        proposal.insert("change_address".to_string(), change_address.clone());
+    }
+    Ok(proposal)
+}
+
+/// Cost of a data output, in HTR units.
+pub const DATA_OUTPUT_COST: u64 = 1;
+
+/// Sum the outputs of a `send-tx` body by token uid, data outputs cost [`DATA_OUTPUT_COST`] HTR.
+///
+/// # Examples
+///
+/// ```
+/// use headless_cli::send::required_by_token;
+///
+/// let token = "ab".repeat(32);
+/// let body = format!(r#"{{"outputs": [
+///     {{ "address": "WewDeXWyvHP7jJTs7tjLoQfoB72LLxJQqN", "value": 100 }},
+///     {{ "address": "WewDeXWyvHP7jJTs7tjLoQfoB72LLxJQqN", "value": 5, "token": "{}" }},
+///     {{ "type": "data", "data": "memo" }}
+/// ]}}"#, token);
+/// let required = required_by_token(&body).unwrap();
+/// assert_eq!(required["00"], 101);
+/// assert_eq!(required[&token], 5);
+/// ```
+pub fn required_by_token(body: &str) -> Result<BTreeMap<String, u64>, HeadlessCliError> {
+    let body: serde_json::Value = serde_json::from_str(body)?;
+    let outputs = body["outputs"]
+        .as_array()
+        .ok_or_else(|| HeadlessCliError::InvalidInput("the body has no outputs".to_string()))?;
+
+    let mut required: BTreeMap<String, u64> = BTreeMap::new();
+    for output in outputs.iter() {
+        let (token, value) = if output["type"] == "data" {
+            (HTR_UID, DATA_OUTPUT_COST)
+        } else {
+            let value = output["value"].as_u64().ok_or_else(|| {
+                HeadlessCliError::InvalidInput(format!("invalid output value: {}", output["value"]))
+            })?;
+            let token = output["token"].as_str().unwrap_or(HTR_UID);
+            (token, value)
+        };
+        let total = required.entry(token.to_string()).or_default();
+        *total = total.checked_add(value).ok_or_else(|| {
+            HeadlessCliError::InvalidInput(format!(
+                "the outputs of token {} add up to more than {}",
+                token,
+                Amount::from_units(u64::MAX)
+            ))
+        })?;
+    }
+    Ok(required)
+}
+
+/// Compare the amount required of each token with the wallet balance.
+///
+/// Fails with [`HeadlessCliError::InsufficientFunds`] listing every token
+/// without enough available funds, locked funds are reported separately
+/// since they cannot be spent yet.
+///
+/// # Examples
+///
+/// ```
+/// use headless_cli::data::BalanceResponse;
+/// use headless_cli::send::check_funds;
+/// use std::collections::BTreeMap;
+///
+/// let required = BTreeMap::from([("00".to_string(), 500)]);
+/// let balances = BTreeMap::from([("00".to_string(), BalanceResponse { available: 300, locked: 400, extra: Default::default() })]);
+/// let err = check_funds(&required, &balances).unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "Insufficient funds: HTR: sending 5.00, available 3.00 (missing 2.00), locked 4.00"
+/// );
+///
+/// let balances = BTreeMap::from([("00".to_string(), BalanceResponse { available: 500, locked: 0, extra: Default::default() })]);
+/// assert!(check_funds(&required, &balances).is_ok());
+/// ```
+pub fn check_funds(
+    required: &BTreeMap<String, u64>,
+    balances: &BTreeMap<String, BalanceResponse>,
+) -> Result<(), HeadlessCliError> {
+    let mut shortfalls = vec![];
+    for (token, value) in required.iter() {
+        let (available, locked) = balances
+            .get(token)
+            .map_or((0, 0), |balance| (balance.available, balance.locked));
+        if available >= *value {
+            continue;
+        }
+
+        let name = if token == HTR_UID { "HTR" } else { token };
+        let mut shortfall = format!(
+            "{}: sending {}, available {} (missing {})",
+            name,
+            Amount::from_units(*value),
+            Amount::from_units(available),
+            Amount::from_units(value - available)
+        );
+        if locked > 0 {
+            shortfall.push_str(&format!(", locked {}", Amount::from_units(locked)));
+        }
+        shortfalls.push(shortfall);
+    }
+
+    if !shortfalls.is_empty() {
+        return Err(HeadlessCliError::InsufficientFunds(shortfalls.join("; ")));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "WewDeXWyvHP7jJTs7tjLoQfoB72LLxJQqN";
+
+    fn output_token(output: SendOutput) -> Option<String> {
+        match output {
+            SendOutput::Value { token, .. } => token,
+            SendOutput::Data(_) => panic!("not a value output"),
+        }
+    }
+
+    #[test]
+    fn output_without_token_uses_the_default_token() {
+        let default = "ab".repeat(32);
+        let spec: OutputSpec = format!("{}:12.34", ADDRESS).parse().unwrap();
+
+        let output = spec.to_output(false, Some(&default), None).unwrap();
+        assert_eq!(output_token(output), Some(default));
+
+        let output = spec.to_output(false, None, None).unwrap();
+        assert_eq!(output_token(output), None);
+    }
+
+    #[test]
+    fn output_token_overrides_the_default_token() {
+        let token = "cd".repeat(32);
+        let spec: OutputSpec = format!("{}:12.34:{}", ADDRESS, token).parse().unwrap();
+
+        let output = spec.to_output(false, Some(&"ab".repeat(32)), None).unwrap();
+        assert_eq!(output_token(output), Some(token));
+    }
+
+    #[test]
+    fn required_by_token_rejects_overflowing_sums() {
+        let body = format!(
+            r#"{{"outputs": [{{"address": "{0}", "value": {1}}}, {{"address": "{0}", "value": 1}}]}}"#,
+            ADDRESS,
+            u64::MAX
+        );
+        assert!(matches!(
+            required_by_token(&body),
+            Err(HeadlessCliError::InvalidInput(_))
+        ));
+
+        // data outputs are also added to HTR
+        let body = format!(
+            r#"{{"outputs": [{{"address": "{}", "value": {}}}, {{"type": "data", "data": "memo"}}]}}"#,
+            ADDRESS,
+            u64::MAX
+        );
+        assert!(required_by_token(&body).is_err());
+    }
+
+    #[test]
+    fn output_symbol_is_checked_against_the_default_token() {
+        let spec: OutputSpec = format!("{}:12HTR", ADDRESS).parse().unwrap();
+
+        assert!(spec.to_output(false, None, None).is_ok());
+        assert!(spec.to_output(false, Some(&"ab".repeat(32)), None).is_err());
+    }
+}
@@ -0,0 +1,813 @@
+//! Offline decoding and hashing of Hathor transactions and blocks.
+//!
+//! Supports blocks (version 0), regular transactions (version 1) and token
+//! creation transactions (version 2) as serialized by hathor-core, so a
+//! transaction hex can be inspected without a running wallet.
+//!
+//! The hash of a transaction is computed from its parts like hathor-core does:
+//! `reverse(sha256(sha256(sha256(funds) || sha256(graph) || nonce)))`, where
+//! the funds struct has the tokens, inputs and outputs and the graph struct
+//! has the weight, timestamp and parents.
+
+use crate::data::{DecodedOutput, DecodedTxInput, HistoryOutput};
+use crate::error::HeadlessCliError;
+use crate::utils::{from_hex, to_hex};
+use crate::validation::{encode_address, AddressType, Network, HTR_UID};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/////////////////////////////////////////// Transactions
+
+pub const BLOCK_VERSION: u8 = 0;
+pub const REGULAR_TRANSACTION_VERSION: u8 = 1;
+pub const TOKEN_CREATION_TRANSACTION_VERSION: u8 = 2;
+
+/// Mask of the token index on `token_data`, the high bit marks authority outputs.
+pub const TOKEN_INDEX_MASK: u8 = 0x7f;
+pub const TOKEN_AUTHORITY_MASK: u8 = 0x80;
+
+/// A transaction id or token uid.
+pub type Hash = [u8; 32];
+
+const OP_GREATERTHAN_TIMESTAMP: u8 = 0x6f;
+const OP_DUP: u8 = 0x76;
+const OP_HASH160: u8 = 0xa9;
+const OP_EQUALVERIFY: u8 = 0x88;
+const OP_EQUAL: u8 = 0x87;
+const OP_CHECKSIG: u8 = 0xac;
+const OP_PUSHDATA1: u8 = 0x4c;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TxInput {
+    pub tx_id: Hash,
+    pub index: u8,
+    /// Signature and public key (or redeem script), empty when not signed yet
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TxOutput {
+    /// Amount in units, or the authority bits of authority outputs
+    pub value: u64,
+    pub token_data: u8,
+    pub script: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenInfo {
+    pub name: String,
+    pub symbol: String,
+}
+
+/// A transaction or block parsed from its serialized bytes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transaction {
+    pub signal_bits: u8,
+    pub version: u8,
+    /// Uids of the tokens of a regular transaction, the index on `token_data` starts at 1
+    pub tokens: Vec<Hash>,
+    pub inputs: Vec<TxInput>,
+    pub outputs: Vec<TxOutput>,
+    /// Token being created by a token creation transaction
+    pub token_info: Option<TokenInfo>,
+    pub weight: f64,
+    pub timestamp: u32,
+    pub parents: Vec<Hash>,
+    /// Extra data of a block
+    pub data: Option<Vec<u8>>,
+    /// 16 bytes on blocks, 4 bytes on transactions
+    pub nonce: u128,
+}
+
+/// Reads big endian values from a byte slice.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], HeadlessCliError> {
+        let end = self.position + length;
+        let slice = self.bytes.get(self.position..end).ok_or_else(|| {
+            HeadlessCliError::InvalidInput(format!(
+                "invalid transaction: expected {} more bytes at position {}",
+                length, self.position
+            ))
+        })?;
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, HeadlessCliError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, HeadlessCliError> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, HeadlessCliError> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn hash(&mut self) -> Result<Hash, HeadlessCliError> {
+        Ok(self.take(32)?.try_into().unwrap())
+    }
+
+    fn string(&mut self) -> Result<String, HeadlessCliError> {
+        let length = self.u8()? as usize;
+        String::from_utf8(self.take(length)?.to_vec()).map_err(|_| {
+            HeadlessCliError::InvalidInput("invalid transaction: token info is not utf-8".into())
+        })
+    }
+
+    /// Values up to 2^31 - 1 use 4 bytes, bigger ones use 8 bytes with the sign inverted.
+    fn output_value(&mut self) -> Result<u64, HeadlessCliError> {
+        let high_byte = *self.bytes.get(self.position).unwrap_or(&0);
+        if high_byte & 0x80 == 0 {
+            return Ok(self.u32()? as u64);
+        }
+        let value = i64::from_be_bytes(self.take(8)?.try_into().unwrap());
+        value
+            .checked_neg()
+            .map(|value| value as u64)
+            .ok_or_else(|| {
+                HeadlessCliError::InvalidInput("invalid transaction: invalid output value".into())
+            })
+    }
+
+    fn input(&mut self) -> Result<TxInput, HeadlessCliError> {
+        let tx_id = self.hash()?;
+        let index = self.u8()?;
+        let length = self.u16()? as usize;
+        Ok(TxInput {
+            tx_id,
+            index,
+            data: self.take(length)?.to_vec(),
+        })
+    }
+
+    fn output(&mut self) -> Result<TxOutput, HeadlessCliError> {
+        let value = self.output_value()?;
+        let token_data = self.u8()?;
+        let length = self.u16()? as usize;
+        Ok(TxOutput {
+            value,
+            token_data,
+            script: self.take(length)?.to_vec(),
+        })
+    }
+}
+
+impl Transaction {
+    /// Parse a transaction or block from its hex serialization.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use headless_cli::tx::Transaction;
+    ///
+    /// // mainnet genesis block
+    /// let block = Transaction::from_hex(concat!(
+    ///     "000001ffffffe8b789180000001976a9147fd4ae0e4fb2d2854e76d359029d8078bb99649e88ac",
+    ///     "40350000000000005e0f84a9000000000000000000000000000000278a7e",
+    /// )).unwrap();
+    /// assert_eq!(block.version, 0);
+    /// assert_eq!(block.outputs[0].value, 100_000_000_000);
+    /// assert_eq!(block.timestamp, 1578075305);
+    /// assert_eq!(block.nonce, 2591358);
+    ///
+    /// assert!(Transaction::from_hex("0001").is_err());
+    /// ```
+    pub fn from_hex(hex: &str) -> Result<Self, HeadlessCliError> {
+        Transaction::parse(&from_hex(hex.trim())?)
+    }
+
+    /// Parse a transaction or block from its serialized bytes.
+    pub fn parse(bytes: &[u8]) -> Result<Self, HeadlessCliError> {
+        let mut reader = Reader { bytes, position: 0 };
+        let signal_bits = reader.u8()?;
+        let version = reader.u8()?;
+
+        let mut tokens = vec![];
+        let mut inputs = vec![];
+        let mut outputs = vec![];
+        let mut token_info = None;
+
+        // Funds: tokens, inputs and outputs
+        match version {
+            BLOCK_VERSION => {
+                let outputs_len = reader.u8()?;
+                for _ in 0..outputs_len {
+                    outputs.push(reader.output()?);
+                }
+            }
+            REGULAR_TRANSACTION_VERSION | TOKEN_CREATION_TRANSACTION_VERSION => {
+                let tokens_len = match version {
+                    REGULAR_TRANSACTION_VERSION => reader.u8()?,
+                    _ => 0,
+                };
+                let inputs_len = reader.u8()?;
+                let outputs_len = reader.u8()?;
+                for _ in 0..tokens_len {
+                    tokens.push(reader.hash()?);
+                }
+                for _ in 0..inputs_len {
+                    inputs.push(reader.input()?);
+                }
+                for _ in 0..outputs_len {
+                    outputs.push(reader.output()?);
+                }
+                if version == TOKEN_CREATION_TRANSACTION_VERSION {
+                    let info_version = reader.u8()?;
+                    if info_version != 1 {
+                        return Err(HeadlessCliError::InvalidInput(format!(
+                            "invalid transaction: unknown token info version {}",
+                            info_version
+                        )));
+                    }
+                    token_info = Some(TokenInfo {
+                        name: reader.string()?,
+                        symbol: reader.string()?,
+                    });
+                }
+            }
+            version => {
+                return Err(HeadlessCliError::InvalidInput(format!(
+                    "unsupported transaction version {}",
+                    version
+                )))
+            }
+        }
+
+        // Graph: weight, timestamp, parents and the data of blocks
+        let weight = f64::from_be_bytes(reader.take(8)?.try_into().unwrap());
+        let timestamp = reader.u32()?;
+        let parents_len = reader.u8()?;
+        let mut parents = vec![];
+        for _ in 0..parents_len {
+            parents.push(reader.hash()?);
+        }
+        let data = match version {
+            BLOCK_VERSION => {
+                let length = reader.u8()? as usize;
+                Some(reader.take(length)?.to_vec())
+            }
+            _ => None,
+        };
+
+        let nonce_len = if version == BLOCK_VERSION { 16 } else { 4 };
+        let nonce = reader
+            .take(nonce_len)?
+            .iter()
+            .fold(0u128, |nonce, byte| (nonce << 8) | *byte as u128);
+
+        let tokens_len = match version {
+            BLOCK_VERSION => 0,
+            TOKEN_CREATION_TRANSACTION_VERSION => 1,
+            _ => tokens.len(),
+        };
+        for (index, output) in outputs.iter().enumerate() {
+            let token_index = (output.token_data & TOKEN_INDEX_MASK) as usize;
+            if token_index > tokens_len {
+                return Err(HeadlessCliError::InvalidInput(format!(
+                    "invalid transaction: output {} has token index {} but there are only {} tokens",
+                    index, token_index, tokens_len
+                )));
+            }
+        }
+
+        if reader.position != bytes.len() {
+            return Err(HeadlessCliError::InvalidInput(format!(
+                "invalid transaction: {} unexpected bytes at the end",
+                bytes.len() - reader.position
+            )));
+        }
+
+        Ok(Transaction {
+            signal_bits,
+            version,
+            tokens,
+            inputs,
+            outputs,
+            token_info,
+            weight,
+            timestamp,
+            parents,
+            data,
+            nonce,
+        })
+    }
+
+    /// Uid of the token of an output, see [`TOKEN_INDEX_MASK`].
+    ///
+    /// The token created by a token creation transaction has the hash of the
+    /// transaction as uid.
+    ///
+    /// # Panics
+    ///
+    /// If the token index is out of range, which [`Transaction::parse`] rejects.
+    pub fn output_token(&self, output: &TxOutput) -> String {
+        match (output.token_data & TOKEN_INDEX_MASK) as usize {
+            0 => HTR_UID.to_string(),
+            1 if self.version == TOKEN_CREATION_TRANSACTION_VERSION => self.hash(),
+            index => to_hex(&self.tokens[index - 1]),
+        }
+    }
+
+    /// Tokens, inputs and outputs (and the token info of token creations).
+    ///
+    /// Without `input_data` the signatures are left out, which is the data
+    /// signed by each input (sighash all).
+    pub fn funds_struct(&self, input_data: bool) -> Vec<u8> {
+        let mut bytes = vec![self.signal_bits, self.version];
+        match self.version {
+            BLOCK_VERSION => bytes.push(self.outputs.len() as u8),
+            _ => {
+                if self.version == REGULAR_TRANSACTION_VERSION {
+                    bytes.push(self.tokens.len() as u8);
+                }
+                bytes.push(self.inputs.len() as u8);
+                bytes.push(self.outputs.len() as u8);
+                for token in &self.tokens {
+                    bytes.extend(token);
+                }
+                for input in &self.inputs {
+                    bytes.extend(input.tx_id);
+                    bytes.push(input.index);
+                    let data: &[u8] = if input_data { &input.data } else { &[] };
+                    bytes.extend((data.len() as u16).to_be_bytes());
+                    bytes.extend(data);
+                }
+            }
+        }
+        for output in &self.outputs {
+            match i32::try_from(output.value) {
+                Ok(value) => bytes.extend(value.to_be_bytes()),
+                Err(_) => bytes.extend((-(output.value as i64)).to_be_bytes()),
+            }
+            bytes.push(output.token_data);
+            bytes.extend((output.script.len() as u16).to_be_bytes());
+            bytes.extend(&output.script);
+        }
+        if let Some(info) = &self.token_info {
+            bytes.push(1);
+            for field in [&info.name, &info.symbol] {
+                bytes.push(field.len() as u8);
+                bytes.extend(field.as_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// Weight, timestamp and parents (and the data of blocks).
+    pub fn graph_struct(&self) -> Vec<u8> {
+        let mut bytes = self.weight.to_be_bytes().to_vec();
+        bytes.extend(self.timestamp.to_be_bytes());
+        bytes.push(self.parents.len() as u8);
+        for parent in &self.parents {
+            bytes.extend(parent);
+        }
+        if let Some(data) = &self.data {
+            bytes.push(data.len() as u8);
+            bytes.extend(data);
+        }
+        bytes
+    }
+
+    fn nonce_bytes(&self) -> Vec<u8> {
+        match self.version {
+            BLOCK_VERSION => self.nonce.to_be_bytes().to_vec(),
+            _ => (self.nonce as u32).to_be_bytes().to_vec(),
+        }
+    }
+
+    /// Serialize back to the bytes accepted by [`Transaction::parse`].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.funds_struct(true);
+        bytes.extend(self.graph_struct());
+        bytes.extend(self.nonce_bytes());
+        bytes
+    }
+
+    /// The transaction id (or block hash), hex encoded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use headless_cli::tx::Transaction;
+    ///
+    /// // mainnet genesis block
+    /// let block = Transaction::from_hex(concat!(
+    ///     "000001ffffffe8b789180000001976a9147fd4ae0e4fb2d2854e76d359029d8078bb99649e88ac",
+    ///     "40350000000000005e0f84a9000000000000000000000000000000278a7e",
+    /// )).unwrap();
+    /// assert_eq!(
+    ///     block.hash(),
+    ///     "000006cb93385b8b87a545a1cbb6197e6caff600c12cc12fc54250d39c8088fc",
+    /// );
+    /// ```
+    pub fn hash(&self) -> String {
+        let mut part1 = Sha256::new();
+        part1.update(Sha256::digest(self.funds_struct(true)));
+        part1.update(Sha256::digest(self.graph_struct()));
+        part1.update(self.nonce_bytes());
+        let mut hash = Sha256::digest(part1.finalize()).to_vec();
+        hash.reverse();
+        to_hex(&hash)
+    }
+
+    /// All the hashes of the transaction, see [`TxHashes`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use headless_cli::tx::Transaction;
+    ///
+    /// let hex = concat!(
+    ///     "0001010103", "abababababababababababababababababababababababababababababababab",
+    ///     "cdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcd", "0100020102",
+    ///     "000003e800001f046553f1006f76a9147fd4ae0e4fb2d2854e76d359029d8078bb99649e88ac",
+    ///     "00000005010017a9147fd4ae0e4fb2d2854e76d359029d8078bb99649e87",
+    ///     "000000010000070568656c6c6fac",
+    ///     "40318000000000006553f10002",
+    ///     "1111111111111111111111111111111111111111111111111111111111111111",
+    ///     "2222222222222222222222222222222222222222222222222222222222222222",
+    ///     "00000007",
+    /// );
+    /// let tx = Transaction::from_hex(hex).unwrap();
+    /// assert_eq!(headless_cli::utils::to_hex(&tx.to_bytes()), hex);
+    ///
+    /// let hashes = tx.hashes();
+    /// assert_eq!(
+    ///     hashes.hash,
+    ///     "4b01d87e3ea543453087f8ec276b27af46463b76340be4ab736d27531295bc15",
+    /// );
+    ///
+    /// // the signatures are not part of the sighash
+    /// let mut unsigned = tx.clone();
+    /// unsigned.inputs[0].data.clear();
+    /// assert_eq!(unsigned.hashes().sighash, hashes.sighash);
+    /// assert_ne!(unsigned.hashes().hash, hashes.hash);
+    /// ```
+    pub fn hashes(&self) -> TxHashes {
+        TxHashes {
+            hash: self.hash(),
+            funds_hash: to_hex(&Sha256::digest(self.funds_struct(true))),
+            graph_hash: to_hex(&Sha256::digest(self.graph_struct())),
+            sighash: to_hex(&Sha256::digest(Sha256::digest(self.funds_struct(false)))),
+        }
+    }
+
+    /// The transaction in the shapes used by the headless, addresses are encoded for `network`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use headless_cli::tx::Transaction;
+    /// use headless_cli::validation::Network;
+    ///
+    /// let tx = Transaction::from_hex(concat!(
+    ///     "0001010103", "abababababababababababababababababababababababababababababababab",
+    ///     "cdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcd", "0100020102",
+    ///     "000003e800001f046553f1006f76a9147fd4ae0e4fb2d2854e76d359029d8078bb99649e88ac",
+    ///     "00000005010017a9147fd4ae0e4fb2d2854e76d359029d8078bb99649e87",
+    ///     "000000010000070568656c6c6fac",
+    ///     "40318000000000006553f10002",
+    ///     "1111111111111111111111111111111111111111111111111111111111111111",
+    ///     "2222222222222222222222222222222222222222222222222222222222222222",
+    ///     "00000007",
+    /// )).unwrap();
+    /// let decoded = tx.decode(Network::Mainnet);
+    ///
+    /// assert_eq!(decoded.inputs[0].index, 1);
+    /// assert_eq!(decoded.inputs[0].signed, Some(true));
+    ///
+    /// let timelocked = &decoded.outputs[0];
+    /// assert_eq!(timelocked.value, 1000);
+    /// assert_eq!(timelocked.token, "00");
+    /// assert_eq!(timelocked.decoded.address.as_deref(), Some("HJB2yxxsHtudGGy3jmVeadwMfRi2zNCKKD"));
+    /// assert_eq!(timelocked.decoded.timelock, Some(1700000000));
+    ///
+    /// let p2sh = &decoded.outputs[1];
+    /// assert_eq!(p2sh.token, "ab".repeat(32));
+    /// assert_eq!(p2sh.decoded.address.as_deref(), Some("hSTE4Ur9tiiCLHLFCwUmgAGZRhAddnGv5m"));
+    ///
+    /// assert_eq!(decoded.outputs[2].decoded.data.as_deref(), Some("hello"));
+    /// ```
+    pub fn decode(&self, network: Network) -> DecodedTransaction {
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|input| DecodedTxInput {
+                tx_id: to_hex(&input.tx_id),
+                index: input.index as u32,
+                decoded: None,
+                token: None,
+                value: None,
+                token_data: None,
+                script: None,
+                signed: Some(!input.data.is_empty()),
+                mine: None,
+                extra: Default::default(),
+            })
+            .collect();
+
+        let outputs = self
+            .outputs
+            .iter()
+            .map(|output| HistoryOutput {
+                value: output.value,
+                token_data: output.token_data,
+                script: to_hex(&output.script),
+                decoded: decode_script(&output.script, network),
+                token: self.output_token(output),
+                spent_by: None,
+                extra: Default::default(),
+            })
+            .collect();
+
+        DecodedTransaction {
+            hash: self.hash(),
+            version: self.version,
+            signal_bits: self.signal_bits,
+            weight: self.weight,
+            timestamp: self.timestamp,
+            nonce: self.nonce.to_string(),
+            parents: self.parents.iter().map(|parent| to_hex(parent)).collect(),
+            tokens: self.tokens.iter().map(|token| to_hex(token)).collect(),
+            inputs,
+            outputs,
+            token_name: self.token_info.as_ref().map(|info| info.name.clone()),
+            token_symbol: self.token_info.as_ref().map(|info| info.symbol.clone()),
+            data: self.data.as_deref().map(to_hex),
+        }
+    }
+}
+
+/// A transaction decoded offline, inputs only have the spent output reference.
+#[derive(Serialize, Debug)]
+pub struct DecodedTransaction {
+    pub hash: String,
+    pub version: u8,
+    pub signal_bits: u8,
+    pub weight: f64,
+    pub timestamp: u32,
+    /// Decimal, block nonces do not fit in a json number
+    pub nonce: String,
+    pub parents: Vec<String>,
+    pub tokens: Vec<String>,
+    pub inputs: Vec<DecodedTxInput>,
+    pub outputs: Vec<HistoryOutput>,
+    pub token_name: Option<String>,
+    pub token_symbol: Option<String>,
+    /// Extra data of a block, hex encoded
+    pub data: Option<String>,
+}
+
+/// Hashes of a transaction computed locally.
+#[derive(Serialize, Debug)]
+pub struct TxHashes {
+    /// Transaction id (or block hash)
+    pub hash: String,
+    /// sha256 of the funds struct (tokens, inputs and outputs)
+    pub funds_hash: String,
+    /// sha256 of the graph struct (weight, timestamp and parents)
+    pub graph_hash: String,
+    /// Double sha256 of the funds struct without the input data, signed by each input
+    pub sighash: String,
+}
+
+/// Compute the hashes of a transaction hex, failing if the hash is not `expected`.
+///
+/// `expected` is the id reported by the headless, e.g. on `transaction` or
+/// `send-tx` responses.
+///
+/// # Examples
+///
+/// ```
+/// use headless_cli::tx::verify_hash;
+///
+/// let genesis = concat!(
+///     "000001ffffffe8b789180000001976a9147fd4ae0e4fb2d2854e76d359029d8078bb99649e88ac",
+///     "40350000000000005e0f84a9000000000000000000000000000000278a7e",
+/// );
+/// let id = "000006cb93385b8b87a545a1cbb6197e6caff600c12cc12fc54250d39c8088fc";
+/// assert_eq!(verify_hash(genesis, None).unwrap().hash, id);
+/// assert!(verify_hash(genesis, Some(&id.to_uppercase())).is_ok());
+/// assert!(verify_hash(genesis, Some(&"00".repeat(32))).is_err());
+/// ```
+pub fn verify_hash(tx_hex: &str, expected: Option<&str>) -> Result<TxHashes, HeadlessCliError> {
+    let hashes = Transaction::from_hex(tx_hex)?.hashes();
+    match expected {
+        Some(expected) if !expected.trim().eq_ignore_ascii_case(&hashes.hash) => {
+            Err(HeadlessCliError::InvalidInput(format!(
+                "hash mismatch: computed {}, expected {}",
+                hashes.hash,
+                expected.trim()
+            )))
+        }
+        _ => Ok(hashes),
+    }
+}
+
+/// Decode a P2PKH, P2SH (both optionally timelocked) or data output script.
+///
+/// Unknown scripts have all fields empty.
+pub fn decode_script(script: &[u8], network: Network) -> DecodedOutput {
+    let mut decoded = DecodedOutput {
+        address: None,
+        timelock: None,
+        data: None,
+        extra: Default::default(),
+    };
+
+    // <timelock> OP_GREATERTHAN_TIMESTAMP prefix
+    let mut rest = script;
+    if script.len() > 6 && script[0] == 4 && script[5] == OP_GREATERTHAN_TIMESTAMP {
+        decoded.timelock = Some(u32::from_be_bytes(script[1..5].try_into().unwrap()) as u64);
+        rest = &script[6..];
+    }
+
+    let address = match rest {
+        [OP_DUP, OP_HASH160, 20, hash @ .., OP_EQUALVERIFY, OP_CHECKSIG] if hash.len() == 20 => {
+            Some((hash, AddressType::P2pkh))
+        }
+        [OP_HASH160, 20, hash @ .., OP_EQUAL] if hash.len() == 20 => {
+            Some((hash, AddressType::P2sh))
+        }
+        _ => None,
+    };
+    if let Some((hash, address_type)) = address {
+        decoded.address = Some(encode_address(
+            hash.try_into().unwrap(),
+            address_type,
+            network,
+        ));
+        return decoded;
+    }
+    decoded.timelock = None;
+
+    // <data> OP_CHECKSIG
+    let data = match script {
+        [length, data @ .., OP_CHECKSIG]
+            if *length < OP_PUSHDATA1 && data.len() == *length as usize =>
+        {
+            Some(data)
+        }
+        [OP_PUSHDATA1, length, data @ .., OP_CHECKSIG] if data.len() == *length as usize => {
+            Some(data)
+        }
+        _ => None,
+    };
+    decoded.data = data.map(|data| String::from_utf8_lossy(data).to_string());
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Mainnet genesis block
+    const GENESIS_BLOCK: &str = concat!(
+        "000001ffffffe8b789180000001976a9147fd4ae0e4fb2d2854e76d359029d8078bb99649e88ac",
+        "40350000000000005e0f84a9000000000000000000000000000000278a7e",
+    );
+    const GENESIS_BLOCK_HASH: &str =
+        "000006cb93385b8b87a545a1cbb6197e6caff600c12cc12fc54250d39c8088fc";
+
+    // REGULAR_TX and TOKEN_CREATION_TX are built by hand, not taken from the
+    // network, and their hashes come from a separate implementation of the
+    // hashing. TODO: replace them with testnet transactions and the tx ids the
+    // full node reports for them.
+
+    // Signed transaction with a custom token, 2 inputs and 2 outputs,
+    // spending the genesis transactions as parents
+    const REGULAR_TX: &str = concat!(
+        "000101020200000000f76262bb1cca969d952ac2f0e85f88ec34c31f26a13eb3c31e29d4edababab",
+        "ababababababababababababababababababababababababababababab0000694600010203040506",
+        "0708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f202122232425262728292a2b2c2d2e",
+        "2f303132333435363738393a3b3c3d3e3f4041424344452102000102030405060708090a0b0c0d0e",
+        "0f101112131415161718191a1b1c1d1e1fcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcd",
+        "cdcdcdcdcdcdcdcdcd03006946000102030405060708090a0b0c0d0e0f101112131415161718191a",
+        "1b1c1d1e1f202122232425262728292a2b2c2d2e2f303132333435363738393a3b3c3d3e3f404142",
+        "4344452102000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f000000",
+        "fa01001976a9147fd4ae0e4fb2d2854e76d359029d8078bb99649e88ac000003e800001976a91411",
+        "1111111111111111111111111111111111111188ac40314000000000006553f100020002d4d2a15d",
+        "ef7604688e1878ab681142a7b155cbe52a6b4e031250ae96db0a0002ad8d1519daaddc8e1a37b14a",
+        "ac0b045129c01832281fb1c02d873c7abbf900000029",
+    );
+
+    // Token creation of "Test Token" (TST) with mint and melt authorities and HTR change
+    const TOKEN_CREATION_TX: &str = concat!(
+        "00020104efefefefefefefefefefefefefefefefefefefefefefefefefefefefefefefef01006946",
+        "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f2021222324252627",
+        "28292a2b2c2d2e2f303132333435363738393a3b3c3d3e3f40414243444521020001020304050607",
+        "08090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f0000271001001976a9147fd4ae0e4fb2",
+        "d2854e76d359029d8078bb99649e88ac0000000181001976a9147fd4ae0e4fb2d2854e76d359029d",
+        "8078bb99649e88ac0000000281001976a9147fd4ae0e4fb2d2854e76d359029d8078bb99649e88ac",
+        "0000038400001976a914111111111111111111111111111111111111111188ac010a546573742054",
+        "6f6b656e0354535440318000000000006553f164020002d4d2a15def7604688e1878ab681142a7b1",
+        "55cbe52a6b4e031250ae96db0a0002ad8d1519daaddc8e1a37b14aac0b045129c01832281fb1c02d",
+        "873c7abbf90000004d",
+    );
+
+    fn assert_wrong_hash_fails(hex: &str) {
+        let err = verify_hash(hex, Some(&"00".repeat(32))).unwrap_err();
+        assert!(err.to_string().contains("hash mismatch"), "{}", err);
+    }
+
+    #[test]
+    fn genesis_block_hash() {
+        let hashes = verify_hash(GENESIS_BLOCK, Some(GENESIS_BLOCK_HASH)).unwrap();
+        assert_eq!(hashes.hash, GENESIS_BLOCK_HASH);
+        assert_wrong_hash_fails(GENESIS_BLOCK);
+    }
+
+    #[test]
+    fn regular_tx_hashes() {
+        let tx = Transaction::from_hex(REGULAR_TX).unwrap();
+        assert_eq!(tx.version, REGULAR_TRANSACTION_VERSION);
+        assert_eq!(tx.inputs.len(), 2);
+        assert_eq!(tx.outputs.len(), 2);
+        assert_eq!(to_hex(&tx.to_bytes()), REGULAR_TX);
+
+        let hashes = tx.hashes();
+        assert_eq!(
+            hashes.hash,
+            "139408f0e825c05cc6429aad8fbb7baa9d2f478ef4fd6d5970d90af2b328de3c"
+        );
+        assert_eq!(
+            hashes.funds_hash,
+            "f915c71dff66eec85359946c581f083b606d2f0f500558d20850450186f20c24"
+        );
+        assert_eq!(
+            hashes.graph_hash,
+            "f3c5e006589027ee2b325b027371da8b290129207bc1ba9b34a473f79b82c3c2"
+        );
+        assert_eq!(
+            hashes.sighash,
+            "7767f55204281dd6fdd3ef22e0285c91a585cdbf23df95c04fce3eb0a48b7ff2"
+        );
+
+        assert!(verify_hash(REGULAR_TX, Some(&hashes.hash)).is_ok());
+        assert_wrong_hash_fails(REGULAR_TX);
+    }
+
+    #[test]
+    fn token_creation_tx_hashes() {
+        let tx = Transaction::from_hex(TOKEN_CREATION_TX).unwrap();
+        assert_eq!(tx.version, TOKEN_CREATION_TRANSACTION_VERSION);
+        assert_eq!(
+            tx.token_info,
+            Some(TokenInfo {
+                name: "Test Token".to_string(),
+                symbol: "TST".to_string(),
+            })
+        );
+        assert_eq!(to_hex(&tx.to_bytes()), TOKEN_CREATION_TX);
+
+        let hashes = tx.hashes();
+        assert_eq!(
+            hashes.hash,
+            "a63d8e0d9215c7867f47e2f03b456b4c47800964e23a1eb99d3e162dfa979e0d"
+        );
+        assert_eq!(
+            hashes.funds_hash,
+            "248057e0e43128e7eec207720b1337bf7ea8dd1e2ca4b856d8fb743ff9cd9258"
+        );
+        assert_eq!(
+            hashes.graph_hash,
+            "0c580e304de72f0e878ab7c384d02d5e97adf144ad5ddce2f6cf15d36820facd"
+        );
+        assert_eq!(
+            hashes.sighash,
+            "fe11254b6557924b4de4f7f40a5ef75ecba929447be13957921aec8b0397a79f"
+        );
+        // the created token has the hash of the transaction as uid
+        assert_eq!(tx.output_token(&tx.outputs[0]), hashes.hash);
+        assert_eq!(tx.output_token(&tx.outputs[3]), HTR_UID);
+
+        assert!(verify_hash(TOKEN_CREATION_TX, Some(&hashes.hash)).is_ok());
+        assert_wrong_hash_fails(TOKEN_CREATION_TX);
+    }
+
+    #[test]
+    fn rejects_out_of_range_token_indexes() {
+        let invalid = [
+            // the only token of the transaction is 1
+            REGULAR_TX.replacen("000000fa01001976", "000000fa02001976", 1),
+            // a token creation only has the created token
+            TOKEN_CREATION_TX.replacen("0000271001001976", "0000271002001976", 1),
+            // including on authority outputs
+            TOKEN_CREATION_TX.replacen("0000000181001976", "0000000182001976", 1),
+            // blocks only have HTR
+            GENESIS_BLOCK.replacen("e8b7891800000019", "e8b7891800010019", 1),
+        ];
+        for hex in invalid {
+            let err = Transaction::from_hex(&hex).unwrap_err();
+            assert!(err.to_string().contains("token index"), "{}", err);
+        }
+    }
+}
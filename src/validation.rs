@@ -0,0 +1,248 @@
+use crate::error::HeadlessCliError;
+use crate::utils::to_hex;
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/////////////////////////////////////////// Validation
+
+/// Uid of the native token (HTR).
+pub const HTR_UID: &str = "00";
+
+/// Decoded length of an address: version byte, hash160 and a 4 bytes checksum.
+const ADDRESS_BYTES: usize = 25;
+
+/// Hathor network, it defines the version bytes of the addresses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    /// Private networks use the testnet version bytes
+    Privatenet,
+}
+
+impl Network {
+    /// Version bytes of the P2PKH and P2SH addresses.
+    pub fn version_bytes(self) -> (u8, u8) {
+        match self {
+            Network::Mainnet => (0x28, 0x64),
+            Network::Testnet | Network::Privatenet => (0x49, 0x87),
+        }
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Network::Mainnet => write!(f, "mainnet"),
+            Network::Testnet => write!(f, "testnet"),
+            Network::Privatenet => write!(f, "privatenet"),
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mainnet" => Ok(Network::Mainnet),
+            "testnet" => Ok(Network::Testnet),
+            "privatenet" => Ok(Network::Privatenet),
+            _ => Err(format!("unknown network: {}", s)),
+        }
+    }
+}
+
+/// Kind of output script an address pays to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressType {
+    P2pkh,
+    P2sh,
+}
+
+/// A base58check address decoded offline.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Address {
+    pub address: String,
+    #[serde(rename = "type")]
+    pub address_type: AddressType,
+    /// Network of the version byte, testnet and privatenet addresses are the same
+    pub network: Network,
+    pub version_byte: u8,
+    /// Hash of the public key (P2PKH) or of the redeem script (P2SH), hex encoded
+    pub hash: String,
+}
+
+/// First 4 bytes of the double sha256 of `data`.
+fn checksum(data: &[u8]) -> [u8; 4] {
+    let hash = Sha256::digest(Sha256::digest(data));
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+fn invalid_address(address: &str, reason: &str) -> HeadlessCliError {
+    HeadlessCliError::InvalidInput(format!("invalid address {}: {}", address, reason))
+}
+
+/// Decode a base58check address, checking its length, checksum and version byte.
+///
+/// # Examples
+///
+/// ```
+/// use headless_cli::validation::{decode_address, AddressType, Network};
+///
+/// let address = decode_address("HJB2yxxsHtudGGy3jmVeadwMfRi2zNCKKD").unwrap();
+/// assert_eq!(address.address_type, AddressType::P2pkh);
+/// assert_eq!(address.network, Network::Mainnet);
+/// assert_eq!(address.hash, "7fd4ae0e4fb2d2854e76d359029d8078bb99649e");
+///
+/// let address = decode_address("wXHLXHHEk2vrxTDH4d8veYo6TMBeUv26Gm").unwrap();
+/// assert_eq!(address.address_type, AddressType::P2sh);
+/// assert_eq!(address.network, Network::Testnet);
+///
+/// // a typo breaks the checksum
+/// assert!(decode_address("HJB2yxxsHtudGGy3jmVeadwMfRi2zNCKKE").is_err());
+/// ```
+pub fn decode_address(address: &str) -> Result<Address, HeadlessCliError> {
+    let bytes = bs58::decode(address)
+        .into_vec()
+        .map_err(|_| invalid_address(address, "not base58"))?;
+    if bytes.len() != ADDRESS_BYTES {
+        return Err(invalid_address(address, "wrong length"));
+    }
+    if checksum(&bytes[..21]) != bytes[21..] {
+        return Err(invalid_address(address, "wrong checksum"));
+    }
+
+    let version_byte = bytes[0];
+    let (address_type, network) = [Network::Mainnet, Network::Testnet]
+        .into_iter()
+        .find_map(|network| match network.version_bytes() {
+            (p2pkh, _) if p2pkh == version_byte => Some((AddressType::P2pkh, network)),
+            (_, p2sh) if p2sh == version_byte => Some((AddressType::P2sh, network)),
+            _ => None,
+        })
+        .ok_or_else(|| {
+            invalid_address(
+                address,
+                &format!("unknown version byte 0x{:02x}", version_byte),
+            )
+        })?;
+
+    Ok(Address {
+        address: address.to_string(),
+        address_type,
+        network,
+        version_byte,
+        hash: to_hex(&bytes[1..21]),
+    })
+}
+
+/// Encode the hash of a public key or script as an address of `network`.
+///
+/// # Examples
+///
+/// ```
+/// use headless_cli::validation::{encode_address, AddressType, Network};
+///
+/// let hash = [0x7f, 0xd4, 0xae, 0x0e, 0x4f, 0xb2, 0xd2, 0x85, 0x4e, 0x76,
+///             0xd3, 0x59, 0x02, 0x9d, 0x80, 0x78, 0xbb, 0x99, 0x64, 0x9e];
+/// let address = encode_address(&hash, AddressType::P2pkh, Network::Mainnet);
+/// assert_eq!(address, "HJB2yxxsHtudGGy3jmVeadwMfRi2zNCKKD");
+/// ```
+pub fn encode_address(hash: &[u8; 20], address_type: AddressType, network: Network) -> String {
+    let (p2pkh, p2sh) = network.version_bytes();
+    let mut bytes = vec![match address_type {
+        AddressType::P2pkh => p2pkh,
+        AddressType::P2sh => p2sh,
+    }];
+    bytes.extend_from_slice(hash);
+    let checksum = checksum(&bytes);
+    bytes.extend_from_slice(&checksum);
+    bs58::encode(bytes).into_string()
+}
+
+/// Check that the address is valid and, if given, belongs to `network`.
+///
+/// # Examples
+///
+/// ```
+/// use headless_cli::validation::{validate_address_network, Network};
+///
+/// let address = "WewDeXWyvHP7jJTs7tjLoQfoB72LLxJQqN";
+/// assert!(validate_address_network(address, Some(Network::Testnet)).is_ok());
+/// assert!(validate_address_network(address, Some(Network::Privatenet)).is_ok());
+/// assert!(validate_address_network(address, None).is_ok());
+/// assert!(validate_address_network(address, Some(Network::Mainnet)).is_err());
+/// ```
+pub fn validate_address_network(
+    address: &str,
+    network: Option<Network>,
+) -> Result<Address, HeadlessCliError> {
+    let decoded = decode_address(address)?;
+    if let Some(network) = network {
+        if decoded.network.version_bytes() != network.version_bytes() {
+            return Err(invalid_address(
+                address,
+                &format!("{} address, expected {}", decoded.network, network),
+            ));
+        }
+    }
+    Ok(decoded)
+}
+
+fn is_hex(value: &str, length: usize) -> bool {
+    value.len() == length && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Check that the address is a valid address of any network (see [`decode_address`]).
+///
+/// # Examples
+///
+/// ```
+/// use headless_cli::validation::validate_address;
+///
+/// assert!(validate_address("WewDeXWyvHP7jJTs7tjLoQfoB72LLxJQqN").is_ok());
+/// assert!(validate_address("WewDeXWyvHP7jJTs7tjLoQfoB72LLxJQq").is_err());
+/// assert!(validate_address("WewDeXWyvHP7jJTs7tjLoQfoB72LLxJQq0").is_err());
+/// ```
+pub fn validate_address(address: &str) -> Result<(), HeadlessCliError> {
+    decode_address(address).map(|_| ())
+}
+
+/// Check that the token uid is `00` (HTR) or 32 bytes hex encoded.
+///
+/// # Examples
+///
+/// ```
+/// use headless_cli::validation::validate_token_uid;
+///
+/// assert!(validate_token_uid("00").is_ok());
+/// assert!(validate_token_uid(&"ab".repeat(32)).is_ok());
+/// assert!(validate_token_uid("HTR").is_err());
+/// ```
+pub fn validate_token_uid(token: &str) -> Result<(), HeadlessCliError> {
+    if token != HTR_UID && !is_hex(token, 64) {
+        return Err(HeadlessCliError::InvalidInput(format!(
+            "invalid token uid: {}",
+            token
+        )));
+    }
+    Ok(())
+}
+
+/// Check that the transaction id is 32 bytes hex encoded.
+pub fn validate_tx_id(tx_id: &str) -> Result<(), HeadlessCliError> {
+    if !is_hex(tx_id, 64) {
+        return Err(HeadlessCliError::InvalidInput(format!(
+            "invalid transaction id: {}",
+            tx_id
+        )));
+    }
+    Ok(())
+}
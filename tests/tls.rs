@@ -0,0 +1,145 @@
+//! Runs the cli against a local TLS headless to check `--ca-cert`,
+//! `--client-cert`/`--client-key` and `--insecure`.
+//!
+//! The certificates of `tests/tls` are signed by `ca.pem`, the server one is
+//! valid for `127.0.0.1`.
+
+use std::env;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::process::{Command, Output};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use openssl::nid::Nid;
+use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod, SslVerifyMode};
+
+const STATUS: &str = r#"{"statusCode": 3, "statusMessage": "Ready", "network": "testnet", "serverUrl": "http://fullnode"}"#;
+
+fn fixture(name: &str) -> String {
+    format!("{}/tests/tls/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+/// Start a headless that answers every request with a ready status.
+///
+/// With `client_auth` the handshake requires a client certificate signed by
+/// the test CA. Each successful handshake sends the common name of the client
+/// certificate (if any) on the returned channel.
+fn tls_headless(client_auth: bool) -> (String, Receiver<Option<String>>) {
+    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+    acceptor
+        .set_private_key_file(fixture("server.key"), SslFiletype::PEM)
+        .unwrap();
+    acceptor
+        .set_certificate_chain_file(fixture("server.pem"))
+        .unwrap();
+    if client_auth {
+        acceptor.set_ca_file(fixture("ca.pem")).unwrap();
+        acceptor.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+    }
+    let acceptor = acceptor.build();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let host = format!("https://{}", listener.local_addr().unwrap());
+    let (handshakes, received) = mpsc::channel();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = acceptor.accept(stream.unwrap()) else {
+                continue;
+            };
+            let client_name = stream.ssl().peer_certificate().and_then(|cert| {
+                let name = cert.subject_name().entries_by_nid(Nid::COMMONNAME).next()?;
+                Some(name.data().as_utf8().ok()?.to_string())
+            });
+            let _ = handshakes.send(client_name);
+
+            let mut request = vec![];
+            let mut buffer = [0u8; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                match stream.read(&mut buffer) {
+                    Ok(read) if read > 0 => request.extend_from_slice(&buffer[..read]),
+                    _ => break,
+                }
+            }
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                STATUS.len(),
+                STATUS
+            );
+            let _ = stream.write_all(response.as_bytes());
+            let _ = stream.shutdown();
+        }
+    });
+
+    (host, received)
+}
+
+/// Run `wallet status` on `host`, ignoring the config file and environment.
+fn wallet_status(host: &str, flags: &[&str]) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_headless_cli"));
+    for (name, _) in env::vars().filter(|(name, _)| name.starts_with("HEADLESS_CLI_")) {
+        command.env_remove(name);
+    }
+    command
+        .env("HEADLESS_CLI_CONFIG", "/dev/null")
+        .args(["--host", host, "--retries", "0", "--timeout", "10"])
+        .args(flags)
+        .args(["-o", "json", "wallet", "-w", "w", "status"])
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn trusts_the_headless_only_with_its_ca() {
+    let (host, handshakes) = tls_headless(false);
+
+    let output = wallet_status(&host, &[]);
+    assert_eq!(output.status.code(), Some(3), "{:?}", output);
+    assert!(handshakes.try_recv().is_err());
+
+    let output = wallet_status(&host, &["--ca-cert", &fixture("ca.pem")]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Ready"));
+    assert_eq!(handshakes.try_recv(), Ok(None));
+}
+
+#[test]
+fn presents_the_client_certificate() {
+    let (host, handshakes) = tls_headless(true);
+    let ca_cert = fixture("ca.pem");
+
+    let output = wallet_status(&host, &["--ca-cert", &ca_cert]);
+    assert_eq!(output.status.code(), Some(3), "{:?}", output);
+    assert!(handshakes.try_recv().is_err());
+
+    let (client_cert, client_key) = (fixture("client.pem"), fixture("client.key"));
+    let output = wallet_status(
+        &host,
+        &[
+            "--ca-cert",
+            &ca_cert,
+            "--client-cert",
+            &client_cert,
+            "--client-key",
+            &client_key,
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        handshakes.try_recv(),
+        Ok(Some("headless-cli-test-client".to_string()))
+    );
+}
+
+#[test]
+fn insecure_accepts_an_unknown_certificate_with_a_warning() {
+    let (host, handshakes) = tls_headless(false);
+
+    let output = wallet_status(&host, &["--insecure"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("WARNING: TLS certificate verification is disabled"));
+    assert_eq!(handshakes.try_recv(), Ok(None));
+}